@@ -1,5 +1,7 @@
+use crate::bit_vector::BitVector;
 use crate::my_bool::MyBool;
 use nalgebra::DMatrix;
+use zeroize::Zeroize;
 
 #[allow(dead_code)]
 fn matrix_is_identity(matrix: &DMatrix<MyBool>) -> bool {
@@ -19,6 +21,7 @@ fn matrix_is_identity(matrix: &DMatrix<MyBool>) -> bool {
     true
 }
 
+#[allow(dead_code)]
 pub(crate) fn matrix_is_zero(matrix: &DMatrix<MyBool>) -> bool {
     for i in 0..matrix.nrows() {
         for j in 0..matrix.ncols() {
@@ -103,6 +106,11 @@ pub(crate) fn try_inverse_matrix(matrix: &DMatrix<MyBool>) -> Option<DMatrix<MyB
     Some(augmented.view((0, n), (n, n)).into())
 }
 
+/// Sets all the elements to zero, for matrices holding secrets
+pub(crate) fn zeroize_matrix(matrix: &mut DMatrix<MyBool>) {
+    matrix.iter_mut().for_each(Zeroize::zeroize);
+}
+
 pub(crate) fn concat_horizontally_mat(original: &mut DMatrix<MyBool>, to_add: &DMatrix<MyBool>) {
     if original.nrows() != to_add.nrows() {
         panic!("The number of rows must be the same");
@@ -130,32 +138,147 @@ pub(crate) fn concat_vertically_mat(original: &mut DMatrix<MyBool>, to_add: &DMa
     }
 }
 
-fn shifted_row(row: &[MyBool], shift: usize) -> Vec<MyBool> {
-    let row_len = row.len();
-    let mut new_row = vec![MyBool::from(false); row_len];
-    for i in 0..row_len {
-        new_row[(i + shift) % row_len] = row[i];
-    }
-    new_row
-}
 pub(crate) fn make_circulant_matrix(
-    row: &[MyBool],
+    row: &BitVector,
     rows: usize,
     cols: usize,
     shift: usize,
 ) -> DMatrix<MyBool> {
     let mut matrix: DMatrix<MyBool> = DMatrix::from_element(rows, cols, MyBool::from(false));
+    let row = row.slice(0..cols);
     for i in 0..rows {
-        let new_row = shifted_row(&row[0..cols], i * shift);
-        for j in 0..cols {
-            matrix[(i, j)] = new_row[j];
+        for j in row.rotate_right(i * shift).ones() {
+            matrix[(i, j)] = MyBool::from(true);
         }
     }
     matrix
 }
 
+/// Top-left `rows * cols` corner of the square circulant matrix whose first row is `row`
+pub(crate) fn make_truncated_circulant_matrix(
+    row: &BitVector,
+    rows: usize,
+    cols: usize,
+) -> DMatrix<MyBool> {
+    let mut matrix: DMatrix<MyBool> = DMatrix::from_element(rows, cols, MyBool::from(false));
+    for i in 0..rows {
+        for j in row.rotate_right(i).ones().filter(|&j| j < cols) {
+            matrix[(i, j)] = MyBool::from(true);
+        }
+    }
+    matrix
+}
+
+/// Square circulant matrix stored as the positions of the ones in its first row,
+/// row `i` being the first row rotated by `i`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "BitVector", into = "BitVector"))]
+pub(crate) struct SparseCirculantMatrix {
+    size: usize,
+    first_row_ones: Vec<usize>,
+}
+
+impl SparseCirculantMatrix {
+    pub(crate) fn from_first_row(row: &BitVector) -> Self {
+        Self {
+            size: row.len(),
+            first_row_ones: row.ones().collect(),
+        }
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    pub(crate) fn weight(&self) -> usize {
+        self.first_row_ones.len()
+    }
+
+    /// Positions of the ones of the first row, followed by dummy positions up to `padded_weight`
+    /// entries, each paired with whether it is a real one. Constant-time code iterates over all
+    /// of them, so that the number of iterations doesn't reveal the weight of the row.
+    pub(crate) fn padded_first_row_ones(
+        &self,
+        padded_weight: usize,
+    ) -> impl Iterator<Item = (usize, bool)> + '_ {
+        assert!(self.weight() <= padded_weight, "Weight above the padded weight");
+        self.first_row_ones
+            .iter()
+            .map(|&index| (index, true))
+            .chain((self.weight()..padded_weight).map(|_| (0, false)))
+    }
+
+    pub(crate) fn first_row(&self) -> BitVector {
+        let mut row = BitVector::zeros(self.size);
+        for &index in &self.first_row_ones {
+            row.set(index, true);
+        }
+        row
+    }
+
+    /// Rows having a one in column `column`
+    pub(crate) fn column_ones(&self, column: usize) -> impl Iterator<Item = usize> + '_ {
+        self.first_row_ones
+            .iter()
+            .map(move |index| (column + self.size - index) % self.size)
+    }
+}
+
+impl Zeroize for SparseCirculantMatrix {
+    fn zeroize(&mut self) {
+        self.first_row_ones.zeroize();
+        self.size = 0;
+    }
+}
+
+impl From<BitVector> for SparseCirculantMatrix {
+    fn from(row: BitVector) -> Self {
+        Self::from_first_row(&row)
+    }
+}
+
+impl From<SparseCirculantMatrix> for BitVector {
+    fn from(matrix: SparseCirculantMatrix) -> Self {
+        matrix.first_row()
+    }
+}
+
+/// Computes `vector * matrix`, `vector` being a row vector
+pub(crate) fn vector_matrix_product(vector: &BitVector, matrix: &DMatrix<MyBool>) -> BitVector {
+    if vector.len() != matrix.nrows() {
+        panic!("The vector length must match the number of rows");
+    }
+    let mut result = BitVector::zeros(matrix.ncols());
+    for row in vector.ones() {
+        for column in 0..matrix.ncols() {
+            if *matrix[(row, column)] {
+                result.flip(column);
+            }
+        }
+    }
+    result
+}
+
+/// Computes `matrix * vector^T`, returned as a vector
+pub(crate) fn matrix_vector_product(matrix: &DMatrix<MyBool>, vector: &BitVector) -> BitVector {
+    if vector.len() != matrix.ncols() {
+        panic!("The vector length must match the number of columns");
+    }
+    let mut result = BitVector::zeros(matrix.nrows());
+    for column in vector.ones() {
+        for row in 0..matrix.nrows() {
+            if *matrix[(row, column)] {
+                result.flip(row);
+            }
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::bit_vector::BitVector;
     use crate::my_bool::MyBool;
     use nalgebra::DMatrix;
 
@@ -221,11 +344,10 @@ mod tests {
 
     #[test]
     fn test_make_circulant_matrix() {
-        let row: Vec<MyBool> = [
+        let row: BitVector = [
             true, false, false, false, false, false, false, true, false, false,
         ]
-        .iter()
-        .map(|x| MyBool::from(*x))
+        .into_iter()
         .collect();
         let matrix = super::make_circulant_matrix(&row, 7, 7, 1);
         let expected_generated_matrix = DMatrix::from_row_slice(
@@ -244,4 +366,44 @@ mod tests {
         );
         assert_eq!(matrix, expected_generated_matrix);
     }
+
+    #[test]
+    fn test_sparse_circulant_matrix() {
+        let row: BitVector = [false, true, true, false, false, false, true]
+            .into_iter()
+            .collect();
+        let dense = super::make_circulant_matrix(&row, 7, 7, 1);
+        let sparse = super::SparseCirculantMatrix::from_first_row(&row);
+        assert_eq!(sparse.weight(), 3);
+        assert_eq!(sparse.first_row(), row);
+        for column in 0..7 {
+            let mut rows: Vec<usize> = sparse.column_ones(column).collect();
+            rows.sort();
+            let expected: Vec<usize> = (0..7).filter(|r| *dense[(*r, column)]).collect();
+            assert_eq!(rows, expected);
+        }
+    }
+
+    #[test]
+    fn test_make_truncated_circulant_matrix() {
+        let row: BitVector = [false, true, true, false, false, false, true]
+            .into_iter()
+            .collect();
+        let dense = super::make_circulant_matrix(&row, 7, 7, 1);
+        let expected: DMatrix<MyBool> = dense.view((0, 0), (5, 3)).into();
+        assert_eq!(super::make_truncated_circulant_matrix(&row, 5, 3), expected);
+    }
+
+    #[test]
+    fn test_vector_matrix_products() {
+        let row: BitVector = [true, true, false, true, false].into_iter().collect();
+        let matrix = super::make_circulant_matrix(&row, 5, 5, 1);
+        let vector: BitVector = [false, true, true, false, false].into_iter().collect();
+        let expected: BitVector = [true, true, false, true, true].into_iter().collect();
+        assert_eq!(super::vector_matrix_product(&vector, &matrix), expected);
+        assert_eq!(
+            super::matrix_vector_product(&matrix.transpose(), &vector),
+            expected
+        );
+    }
 }
@@ -0,0 +1,400 @@
+use std::fmt::Display;
+use std::ops::{BitAnd, BitAndAssign, BitXor, BitXorAssign, Range};
+use zeroize::Zeroize;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+fn words_for(len: usize) -> usize {
+    len.div_ceil(WORD_BITS)
+}
+
+/// Vector over GF(2), packed 64 bits per word.
+///
+/// Bit `i` is stored in word `i / 64` at position `i % 64`. Bits of the last word beyond `len`
+/// are always kept to zero, so that equality and weight can work on whole words.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BitVector {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVector {
+    pub fn zeros(len: usize) -> Self {
+        Self {
+            words: vec![0u64; words_for(len)],
+            len,
+        }
+    }
+
+    /// Builds a vector of `len` bits from bytes, bit `i` being bit `i & 7` of byte `i >> 3`.
+    /// Missing bytes are read as zeros, extra bits are ignored.
+    pub fn from_bytes(bytes: &[u8], len: usize) -> Self {
+        let mut vector = Self::zeros(len);
+        for (byte_pos, byte) in bytes.iter().take(len.div_ceil(8)).enumerate() {
+            vector.words[byte_pos / 8] |= (*byte as u64) << ((byte_pos % 8) << 3);
+        }
+        vector.clear_tail();
+        vector
+    }
+
+    /// Inverse of [`BitVector::from_bytes`], returns `ceil(len / 8)` bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        (0..self.len.div_ceil(8))
+            .map(|byte_pos| (self.words[byte_pos / 8] >> ((byte_pos % 8) << 3)) as u8)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        assert!(index < self.len, "Index out of bounds");
+        (self.words[index / WORD_BITS] >> (index % WORD_BITS)) & 1 == 1
+    }
+
+    pub fn set(&mut self, index: usize, value: bool) {
+        assert!(index < self.len, "Index out of bounds");
+        let mask = 1u64 << (index % WORD_BITS);
+        if value {
+            self.words[index / WORD_BITS] |= mask;
+        } else {
+            self.words[index / WORD_BITS] &= !mask;
+        }
+    }
+
+    pub fn flip(&mut self, index: usize) {
+        assert!(index < self.len, "Index out of bounds");
+        self.words[index / WORD_BITS] ^= 1u64 << (index % WORD_BITS);
+    }
+
+    /// Hamming weight
+    pub fn weight(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = bool> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+
+    /// Positions of the bits set to one, in increasing order
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(word_pos, word)| {
+                let mut word = *word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some(word_pos * WORD_BITS + bit)
+                })
+            })
+    }
+
+    /// Copy of bits `range.start..range.end`
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        assert!(range.start <= range.end && range.end <= self.len, "Invalid range");
+        let len = range.end - range.start;
+        let mut sliced = Self {
+            words: (0..words_for(len))
+                .map(|k| self.read_word(range.start + k * WORD_BITS))
+                .collect(),
+            len,
+        };
+        sliced.clear_tail();
+        sliced
+    }
+
+    /// `self` followed by `other`
+    pub fn concat(&self, other: &Self) -> Self {
+        let len = self.len + other.len;
+        let mut result = self.clone();
+        result.words.resize(words_for(len), 0);
+        let (word_offset, bit_offset) = (self.len / WORD_BITS, self.len % WORD_BITS);
+        for (k, word) in other.words.iter().enumerate() {
+            result.words[word_offset + k] |= word << bit_offset;
+            if bit_offset != 0 && word_offset + k + 1 < result.words.len() {
+                result.words[word_offset + k + 1] |= word >> (WORD_BITS - bit_offset);
+            }
+        }
+        result.len = len;
+        result
+    }
+
+    /// Cyclic rotation, bit `i` moves to position `(i + shift) % len`
+    pub fn rotate_right(&self, shift: usize) -> Self {
+        if self.len == 0 {
+            return self.clone();
+        }
+        let shift = shift % self.len;
+        if shift == 0 {
+            return self.clone();
+        }
+        let split = self.len - shift;
+        self.slice(split..self.len).concat(&self.slice(0..split))
+    }
+
+    /// Cyclic rotation, bit `i` moves to position `(i - shift) % len`
+    pub fn rotate_left(&self, shift: usize) -> Self {
+        if self.len == 0 {
+            return self.clone();
+        }
+        self.rotate_right(self.len - shift % self.len)
+    }
+
+    /// Same as [`BitVector::rotate_right`], with a running time independent of `shift`, which must
+    /// be at most `len`
+    pub fn rotate_right_constant_time(&self, shift: usize) -> Self {
+        assert!(shift <= self.len, "Shift out of bounds");
+        let mut result = self.clone();
+        let mut stage = 0;
+        while (1usize << stage) <= self.len {
+            let rotated = result.rotate_right(1usize << stage);
+            result.conditional_assign(&rotated, (shift >> stage) & 1 == 1);
+            stage += 1;
+        }
+        result
+    }
+
+    /// Same as [`BitVector::rotate_left`], with a running time independent of `shift`, which must
+    /// be at most `len`
+    pub fn rotate_left_constant_time(&self, shift: usize) -> Self {
+        assert!(shift <= self.len, "Shift out of bounds");
+        self.rotate_right_constant_time(self.len - shift)
+    }
+
+    /// Replaces `self` by `other` if `choice` is true, without branching on `choice`
+    pub fn conditional_assign(&mut self, other: &Self, choice: bool) {
+        assert_eq!(self.len, other.len, "Vectors must have the same length");
+        let mask = 0u64.wrapping_sub(choice as u64);
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word ^= (*word ^ other_word) & mask;
+        }
+    }
+
+    /// Xors `other` into `self` if `choice` is true, without branching on `choice`
+    pub fn conditional_xor_assign(&mut self, other: &Self, choice: bool) {
+        assert_eq!(self.len, other.len, "Vectors must have the same length");
+        let mask = 0u64.wrapping_sub(choice as u64);
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word ^= other_word & mask;
+        }
+    }
+
+    /// Equality whose running time only depends on the lengths, unlike `==` which stops at the
+    /// first differing word
+    pub fn constant_time_eq(&self, other: &Self) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        let difference = self
+            .words
+            .iter()
+            .zip(other.words.iter())
+            .fold(0u64, |difference, (word, other_word)| difference | (word ^ other_word));
+        // Set the top bit if any bit of `difference` is set, without comparing it to zero
+        ((difference | difference.wrapping_neg()) >> (u64::BITS - 1)) == 0
+    }
+
+    /// Flips bit `index` if `value` is true, without branching on `value`
+    pub fn xor_bit(&mut self, index: usize, value: bool) {
+        assert!(index < self.len, "Index out of bounds");
+        self.words[index / WORD_BITS] ^= (value as u64) << (index % WORD_BITS);
+    }
+
+    /// 64 bits starting at bit `start`, reading zeros past the end
+    fn read_word(&self, start: usize) -> u64 {
+        let (word_pos, bit_offset) = (start / WORD_BITS, start % WORD_BITS);
+        let low = self.words.get(word_pos).copied().unwrap_or(0) >> bit_offset;
+        if bit_offset == 0 {
+            return low;
+        }
+        low | (self.words.get(word_pos + 1).copied().unwrap_or(0) << (WORD_BITS - bit_offset))
+    }
+
+    fn clear_tail(&mut self) {
+        let used_bits = self.len % WORD_BITS;
+        if used_bits != 0 {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1u64 << used_bits) - 1;
+            }
+        }
+    }
+}
+
+/// Wipes the bits, leaving an empty vector
+impl Zeroize for BitVector {
+    fn zeroize(&mut self) {
+        self.words.zeroize();
+        self.len = 0;
+    }
+}
+
+impl FromIterator<bool> for BitVector {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut vector = Self::default();
+        for bit in iter {
+            if vector.len % WORD_BITS == 0 {
+                vector.words.push(0);
+            }
+            vector.len += 1;
+            if bit {
+                vector.flip(vector.len - 1);
+            }
+        }
+        vector
+    }
+}
+
+impl Display for BitVector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for bit in self.iter() {
+            write!(f, "{}", if bit { '1' } else { '0' })?;
+        }
+        Ok(())
+    }
+}
+
+impl BitXorAssign<&BitVector> for BitVector {
+    fn bitxor_assign(&mut self, rhs: &BitVector) {
+        assert_eq!(self.len, rhs.len, "Vectors must have the same length");
+        for (word, rhs_word) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *word ^= rhs_word;
+        }
+    }
+}
+
+impl BitXor for &BitVector {
+    type Output = BitVector;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        let mut result = self.clone();
+        result ^= rhs;
+        result
+    }
+}
+
+impl BitAndAssign<&BitVector> for BitVector {
+    fn bitand_assign(&mut self, rhs: &BitVector) {
+        assert_eq!(self.len, rhs.len, "Vectors must have the same length");
+        for (word, rhs_word) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *word &= rhs_word;
+        }
+    }
+}
+
+impl BitAnd for &BitVector {
+    type Output = BitVector;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        let mut result = self.clone();
+        result &= rhs;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitVector;
+
+    fn naive(bits: &[bool]) -> BitVector {
+        bits.iter().copied().collect()
+    }
+
+    fn pseudo_random_bits(len: usize, seed: u64) -> Vec<bool> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                state >> 63 == 1
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rotate() {
+        for len in [1usize, 7, 63, 64, 65, 130, 201] {
+            let bits = pseudo_random_bits(len, len as u64);
+            let vector = naive(&bits);
+            for shift in [0usize, 1, 5, 63, 64, 65, len, 3 * len + 2] {
+                let mut expected = vec![false; len];
+                for i in 0..len {
+                    expected[(i + shift) % len] = bits[i];
+                }
+                assert_eq!(vector.rotate_right(shift), naive(&expected));
+                assert_eq!(vector.rotate_right(shift).rotate_left(shift), vector);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotate_constant_time() {
+        for len in [1usize, 63, 64, 130, 201] {
+            let vector = naive(&pseudo_random_bits(len, 7));
+            for shift in [0usize, 1, 33, len / 2, len - 1, len] {
+                if shift > len {
+                    continue;
+                }
+                assert_eq!(vector.rotate_right_constant_time(shift), vector.rotate_right(shift));
+                assert_eq!(vector.rotate_left_constant_time(shift), vector.rotate_left(shift));
+            }
+        }
+    }
+
+    #[test]
+    fn test_slice_and_concat() {
+        let bits = pseudo_random_bits(300, 42);
+        let vector = naive(&bits);
+        for (start, end) in [(0usize, 0usize), (0, 64), (3, 70), (64, 300), (129, 131), (0, 300)] {
+            let sliced = vector.slice(start..end);
+            assert_eq!(sliced, naive(&bits[start..end]));
+            let rest = vector.slice(end..300);
+            assert_eq!(vector.slice(0..start).concat(&sliced).concat(&rest), vector);
+        }
+    }
+
+    #[test]
+    fn test_bitwise_operations() {
+        let a_bits = pseudo_random_bits(150, 1);
+        let b_bits = pseudo_random_bits(150, 2);
+        let (a, b) = (naive(&a_bits), naive(&b_bits));
+        let xor: Vec<bool> = a_bits.iter().zip(&b_bits).map(|(x, y)| x ^ y).collect();
+        let and: Vec<bool> = a_bits.iter().zip(&b_bits).map(|(x, y)| x & y).collect();
+        assert_eq!(&a ^ &b, naive(&xor));
+        assert_eq!(&a & &b, naive(&and));
+        assert_eq!(a.weight(), a_bits.iter().filter(|x| **x).count());
+        assert_eq!(
+            a.ones().collect::<Vec<usize>>(),
+            (0..150).filter(|i| a_bits[*i]).collect::<Vec<usize>>()
+        );
+        assert!((&a ^ &a).is_zero());
+        assert!(a.constant_time_eq(&a.clone()) && !a.constant_time_eq(&b));
+        assert!(!a.constant_time_eq(&a.slice(0..149)));
+        let mut c = a.clone();
+        c.conditional_xor_assign(&b, false);
+        assert_eq!(c, a);
+        c.conditional_xor_assign(&b, true);
+        assert_eq!(c, naive(&xor));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes = [0b1010_0001u8, 0xff, 0x42];
+        let vector = BitVector::from_bytes(&bytes, 20);
+        assert!(vector.get(0) && !vector.get(1) && vector.get(5) && vector.get(7));
+        assert_eq!(vector.weight(), 3 + 8 + 1);
+        assert_eq!(vector.to_bytes(), vec![0b1010_0001u8, 0xff, 0x02]);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::certificateless_qc_mdpc::wire_format::ObjectType;
+use crate::{Error, Params};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+const LINE_LENGTH: usize = 64;
+const PARAMS_HEADER: &str = "Params: ";
+
+/// PEM-like encoding of `bytes`, the wire format of an object:
+///
+/// ```text
+/// -----BEGIN DPQCLE <LABEL>-----
+/// Params: n_0=2 p=8053 ...
+///
+/// <base64, 64 characters per line>
+/// =<base64 of the OpenPGP CRC-24 of the bytes>
+/// -----END DPQCLE <LABEL>-----
+/// ```
+///
+/// The parameter set is written in clear so that the text can be parsed on its own, the wire
+/// format header still identifies it.
+pub(super) fn armor(label: &str, params: &Params, bytes: &[u8]) -> String {
+    let body = STANDARD.encode(bytes);
+    let mut armored = format!("-----BEGIN DPQCLE {}-----\n", label);
+    armored.push_str(&format!("{}{}\n\n", PARAMS_HEADER, params));
+    // Base64 is ASCII, so any byte position is a character boundary
+    for line_start in (0..body.len()).step_by(LINE_LENGTH) {
+        armored.push_str(&body[line_start..(line_start + LINE_LENGTH).min(body.len())]);
+        armored.push('\n');
+    }
+    armored.push('=');
+    armored.push_str(&STANDARD.encode(&crc24(bytes).to_be_bytes()[1..]));
+    armored.push_str(&format!("\n-----END DPQCLE {}-----\n", label));
+    armored
+}
+
+/// Inverse of [`armor`], returns the parameter set, once validated, and the bytes
+pub(super) fn dearmor(text: &str, label: &str) -> Result<(Params, Vec<u8>), Error> {
+    let mut lines = text.trim().lines().map(str::trim_end);
+    if lines.next() != Some(format!("-----BEGIN DPQCLE {}-----", label).as_str()) {
+        return Err(Error::InvalidEncoding("missing or unexpected armor header"));
+    }
+    let params = lines
+        .next()
+        .and_then(|line| line.strip_prefix(PARAMS_HEADER))
+        .ok_or(Error::InvalidEncoding("missing armor parameters"))?
+        .parse::<Params>()?;
+    params.validate()?;
+    if lines.next() != Some("") {
+        return Err(Error::InvalidEncoding("missing blank line after the armor headers"));
+    }
+    let mut body = String::new();
+    let checksum = loop {
+        match lines.next() {
+            None => return Err(Error::InvalidEncoding("missing armor checksum")),
+            Some(line) => match line.strip_prefix('=') {
+                Some(checksum) => break checksum,
+                None => body.push_str(line),
+            },
+        }
+    };
+    if lines.next() != Some(format!("-----END DPQCLE {}-----", label).as_str())
+        || lines.next().is_some()
+    {
+        return Err(Error::InvalidEncoding("missing or unexpected armor footer"));
+    }
+    let bytes = STANDARD
+        .decode(body)
+        .map_err(|_| Error::InvalidEncoding("invalid armor base64"))?;
+    let checksum = STANDARD
+        .decode(checksum)
+        .map_err(|_| Error::InvalidEncoding("invalid armor checksum"))?;
+    if checksum != crc24(&bytes).to_be_bytes()[1..] {
+        return Err(Error::InvalidEncoding("armor checksum mismatch"));
+    }
+    Ok((params, bytes))
+}
+
+/// [`dearmor`], failing unless the parameter set matches `expected_params` on the fields the
+/// object depends on
+pub(super) fn dearmor_expecting(
+    text: &str,
+    label: &str,
+    object_type: ObjectType,
+    expected_params: &Params,
+) -> Result<Vec<u8>, Error> {
+    let (params, bytes) = dearmor(text, label)?;
+    if object_type.params_identifier(&params) != object_type.params_identifier(expected_params) {
+        return Err(Error::ParametersMismatch);
+    }
+    Ok(bytes)
+}
+
+/// CRC-24 of OpenPGP ASCII armor (RFC 4880, section 6.1)
+fn crc24(bytes: &[u8]) -> u32 {
+    const CRC24_INIT: u32 = 0xb704ce;
+    const CRC24_POLY: u32 = 0x1864cfb;
+    let mut crc = CRC24_INIT;
+    for &byte in bytes {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+    crc & 0xffffff
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::certificateless_qc_mdpc::wire_format::ObjectType;
+    use crate::{Error, Params};
+
+    #[test]
+    fn test_armor() {
+        // RFC 4880 test vector
+        assert_eq!(super::crc24(b"123456789"), 0x21cf02);
+
+        let params = Params::P12323;
+        let bytes: Vec<u8> = (0..=255).collect();
+        let armored = super::armor("TEST", &params, &bytes);
+        assert!(armored.lines().all(|line| line.len() <= 64 || line.starts_with("Params")));
+        assert_eq!(super::dearmor(&armored, "TEST"), Ok((params, bytes.clone())));
+        assert!(super::dearmor(&armored, "OTHER").is_err());
+        let tampered = armored.replacen("AAEC", "AAED", 1);
+        assert!(super::dearmor(&tampered, "TEST").is_err());
+
+        let object_type = ObjectType::PublicKey;
+        assert_eq!(
+            super::dearmor_expecting(&armored, "TEST", object_type, &params),
+            Ok(bytes.clone())
+        );
+        assert_eq!(
+            super::dearmor_expecting(&armored, "TEST", object_type, &Params::default()),
+            Err(Error::ParametersMismatch)
+        );
+        let other_kks = Params { sig_r: 1000, ..params };
+        assert_eq!(
+            super::dearmor_expecting(&armored, "TEST", object_type, &other_kks),
+            Ok(bytes.clone())
+        );
+        assert_eq!(
+            super::dearmor_expecting(&armored, "TEST", ObjectType::Witness, &other_kks),
+            Err(Error::ParametersMismatch)
+        );
+        // Used to underflow when computing the maximum message size
+        let invalid = super::armor("TEST", &Params { p: 7, ..params }, &bytes);
+        assert!(matches!(
+            super::dearmor(&invalid, "TEST"),
+            Err(Error::InvalidParameters(_))
+        ));
+        let huge_params = Params {
+            sig_r: usize::MAX,
+            sig_k: usize::MAX,
+            ..params
+        };
+        let huge = super::armor("TEST", &huge_params, &bytes);
+        assert!(super::dearmor(&huge, "TEST").is_err());
+    }
+}
@@ -0,0 +1,422 @@
+use crate::bit_vector::BitVector;
+use crate::certificateless_qc_mdpc::encryption_private_key::CertificatelessQcMdpcPrivateKey;
+use crate::Error;
+
+/// Recovers the error vector matching a syndrome, using the parity-check matrix of a private key
+pub trait Decoder {
+    fn decode(
+        &self,
+        private_key: &CertificatelessQcMdpcPrivateKey,
+        syndrome: &BitVector,
+    ) -> Result<BitVector, Error>;
+}
+
+/// Decoding algorithm used by a private key, with its default configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecodingAlgorithm {
+    /// [`BitFlippingDecoder`]
+    #[default]
+    BitFlipping,
+    /// [`BlackGrayFlipDecoder`]
+    BlackGrayFlip,
+    /// [`ConstantTimeBitFlippingDecoder`]
+    ConstantTimeBitFlipping,
+}
+
+impl Decoder for DecodingAlgorithm {
+    fn decode(
+        &self,
+        private_key: &CertificatelessQcMdpcPrivateKey,
+        syndrome: &BitVector,
+    ) -> Result<BitVector, Error> {
+        match self {
+            DecodingAlgorithm::BitFlipping => {
+                BitFlippingDecoder::default().decode(private_key, syndrome)
+            }
+            DecodingAlgorithm::BlackGrayFlip => {
+                BlackGrayFlipDecoder::default().decode(private_key, syndrome)
+            }
+            DecodingAlgorithm::ConstantTimeBitFlipping => {
+                ConstantTimeBitFlippingDecoder::default().decode(private_key, syndrome)
+            }
+        }
+    }
+}
+
+/// How the flipping threshold is computed at each iteration
+#[derive(Debug, Clone, Copy)]
+pub enum ThresholdPolicy {
+    /// Maximum unsatisfied counter minus the given delta, or 0
+    MaxCounterMinus(usize),
+    /// Same threshold at every iteration
+    Fixed(usize),
+    /// Function of the syndrome weight and of the unsatisfied counters
+    Custom(fn(usize, &[usize]) -> usize),
+}
+
+impl ThresholdPolicy {
+    pub fn threshold(&self, syndrome_weight: usize, unsatisfied: &[usize]) -> usize {
+        match self {
+            ThresholdPolicy::MaxCounterMinus(delta) => unsatisfied
+                .iter()
+                .max()
+                .copied()
+                .unwrap_or(0)
+                .saturating_sub(*delta),
+            ThresholdPolicy::Fixed(threshold) => *threshold,
+            ThresholdPolicy::Custom(threshold_function) => {
+                threshold_function(syndrome_weight, unsatisfied)
+            }
+        }
+    }
+}
+
+/// What to do with positions whose counter is exactly the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieHandling {
+    Flip,
+    Keep,
+}
+
+impl TieHandling {
+    fn should_flip(&self, counter: usize, threshold: usize) -> bool {
+        match self {
+            TieHandling::Flip => counter >= threshold,
+            TieHandling::Keep => counter > threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DecoderConfig {
+    pub max_iterations: usize,
+    pub threshold: ThresholdPolicy,
+    pub tie_handling: TieHandling,
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        Self {
+            max_iterations: 10,
+            threshold: ThresholdPolicy::MaxCounterMinus(5),
+            tie_handling: TieHandling::Keep,
+        }
+    }
+}
+
+/// Flips, at each iteration, every position whose unsatisfied counter reaches the threshold
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BitFlippingDecoder {
+    pub config: DecoderConfig,
+}
+
+impl BitFlippingDecoder {
+    pub fn new(config: DecoderConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Decoder for BitFlippingDecoder {
+    fn decode(
+        &self,
+        private_key: &CertificatelessQcMdpcPrivateKey,
+        syndrome: &BitVector,
+    ) -> Result<BitVector, Error> {
+        let mut error = BitVector::zeros(private_key.encoded_vector_size());
+        let mut syn = syndrome.clone();
+        for _i in 0..self.config.max_iterations {
+            if syn.is_zero() {
+                return Ok(error);
+            }
+            let unsatisfied = private_key.unsatisfied_parity_checks(&syn);
+            let threshold = self.config.threshold.threshold(syn.weight(), &unsatisfied);
+            for (j, counter) in unsatisfied.into_iter().enumerate() {
+                if self.config.tie_handling.should_flip(counter, threshold) {
+                    error.flip(j);
+                    private_key.flip_syndrome_column(&mut syn, j);
+                }
+            }
+        }
+        if syn.is_zero() {
+            return Ok(error);
+        }
+        Err(Error::DecodingFailure)
+    }
+}
+
+/// Same decision rule as the default [`BitFlippingDecoder`], but its running time and memory
+/// accesses depend neither on the private key nor on the syndrome: it always runs `iterations`
+/// iterations, counters are computed with rotations by the secret positions, padded with discarded
+/// rotations up to [`Params::max_block_weight`](crate::Params::max_block_weight) per block so that
+/// the weight of the blocks isn't revealed, and flips are applied with masks.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantTimeBitFlippingDecoder {
+    pub iterations: usize,
+    pub max_counter_delta: usize,
+}
+
+impl Default for ConstantTimeBitFlippingDecoder {
+    fn default() -> Self {
+        Self {
+            iterations: 10,
+            max_counter_delta: 5,
+        }
+    }
+}
+
+impl Decoder for ConstantTimeBitFlippingDecoder {
+    fn decode(
+        &self,
+        private_key: &CertificatelessQcMdpcPrivateKey,
+        syndrome: &BitVector,
+    ) -> Result<BitVector, Error> {
+        let n = private_key.encoded_vector_size();
+        let mut error = BitVector::zeros(n);
+        let mut syn = syndrome.clone();
+        for _i in 0..self.iterations {
+            let unsatisfied = unsatisfied_parity_checks_constant_time(private_key, &syn);
+            let max_counter = unsatisfied
+                .iter()
+                .fold(0, |max, counter| constant_time_max(max, *counter));
+            let threshold =
+                constant_time_max(max_counter, self.max_counter_delta) - self.max_counter_delta;
+            let mut flips = BitVector::zeros(n);
+            for (j, counter) in unsatisfied.into_iter().enumerate() {
+                flips.xor_bit(j, constant_time_greater(counter, threshold));
+            }
+            error ^= &flips;
+            syn ^= &private_key.syndrome(&flips);
+        }
+        if syn.is_zero() {
+            return Ok(error);
+        }
+        Err(Error::DecodingFailure)
+    }
+}
+
+fn unsatisfied_parity_checks_constant_time(
+    private_key: &CertificatelessQcMdpcPrivateKey,
+    syndrome: &BitVector,
+) -> Vec<usize> {
+    let block_size = syndrome.len();
+    let mut unsatisfied = vec![0usize; private_key.encoded_vector_size()];
+    for (block_pos, block) in private_key.parity_check_blocks.iter().enumerate() {
+        let counters = &mut unsatisfied[block_pos * block_size..(block_pos + 1) * block_size];
+        for (index, is_one) in block.padded_first_row_ones(private_key.padded_block_weight) {
+            let rotated = syndrome.rotate_right_constant_time(index);
+            for (counter, bit) in counters.iter_mut().zip(rotated.iter()) {
+                *counter += (bit & is_one) as usize;
+            }
+        }
+    }
+    unsatisfied
+}
+
+/// `a > b`, both being lower than 2^(usize::BITS - 1)
+fn constant_time_greater(a: usize, b: usize) -> bool {
+    (b.wrapping_sub(a) >> (usize::BITS - 1)) == 1
+}
+
+fn constant_time_max(a: usize, b: usize) -> usize {
+    let mask = 0usize.wrapping_sub(constant_time_greater(b, a) as usize);
+    a ^ ((a ^ b) & mask)
+}
+
+/// Black-Gray-Flip decoder, as in BIKE, with per-block thresholds floored at the block's maximum
+/// counter minus `max_counter_delta`. It fails on about 7% of the errors with
+/// [`crate::Params::P8053`], so the 2^-128 rate of `security_assessments/dfr.py` is not reached.
+#[derive(Debug, Clone, Copy)]
+pub struct BlackGrayFlipDecoder {
+    pub max_iterations: usize,
+    /// Positions whose counter is this close to the threshold are gray
+    pub gray_gap: usize,
+    pub max_counter_delta: usize,
+}
+
+impl Default for BlackGrayFlipDecoder {
+    fn default() -> Self {
+        Self {
+            max_iterations: 10,
+            gray_gap: 3,
+            max_counter_delta: 5,
+        }
+    }
+}
+
+impl Decoder for BlackGrayFlipDecoder {
+    fn decode(
+        &self,
+        private_key: &CertificatelessQcMdpcPrivateKey,
+        syndrome: &BitVector,
+    ) -> Result<BitVector, Error> {
+        let n = private_key.encoded_vector_size();
+        let block_size = syndrome.len();
+
+        let mut error = BitVector::zeros(n);
+        let mut syn = syndrome.clone();
+        for iteration in 0..self.max_iterations {
+            let unsatisfied = private_key.unsatisfied_parity_checks(&syn);
+            let thresholds = self.thresholds(private_key, syn.weight(), &unsatisfied);
+            let mut black = BitVector::zeros(n);
+            let mut gray = BitVector::zeros(n);
+            for (j, counter) in unsatisfied.into_iter().enumerate() {
+                let threshold = thresholds[j / block_size];
+                if counter >= threshold {
+                    error.flip(j);
+                    private_key.flip_syndrome_column(&mut syn, j);
+                    black.set(j, true);
+                } else if counter + self.gray_gap >= threshold {
+                    gray.set(j, true);
+                }
+            }
+
+            if iteration == 0 {
+                for mask in [&black, &gray] {
+                    let unsatisfied = private_key.unsatisfied_parity_checks(&syn);
+                    for j in mask.ones() {
+                        let column_weight =
+                            private_key.parity_check_blocks[j / block_size].weight();
+                        if unsatisfied[j] > ((column_weight + 1) >> 1) {
+                            error.flip(j);
+                            private_key.flip_syndrome_column(&mut syn, j);
+                        }
+                    }
+                }
+            }
+
+            if syn.is_zero() {
+                return Ok(error);
+            }
+        }
+        Err(Error::DecodingFailure)
+    }
+}
+
+impl BlackGrayFlipDecoder {
+    /// Flipping threshold of each block for a given syndrome weight, adapted from Sendrier and
+    /// Vasseur, "On the decoding failure rate of QC-MDPC bit-flipping decoders"
+    fn thresholds(
+        &self,
+        private_key: &CertificatelessQcMdpcPrivateKey,
+        syndrome_weight: usize,
+        unsatisfied: &[usize],
+    ) -> Vec<usize> {
+        let n = private_key.encoded_vector_size();
+        let blocks_count = private_key.parity_check_blocks.len();
+        let r = n / blocks_count;
+        let w = private_key.weight();
+        let d = w / blocks_count;
+        let t = private_key.errors_count();
+
+        // Expected excess of unsatisfied equations involving an odd number of errors
+        let x: f64 = (1..=w.min(t))
+            .step_by(2)
+            .map(|l| {
+                ((l - 1) * r) as f64
+                    * (ln_binomial(w, l) + ln_binomial(n - w, t - l) - ln_binomial(n, t)).exp()
+            })
+            .sum();
+        let s = syndrome_weight as f64;
+        let pi_1 = (s + x) / (t * d) as f64;
+        let pi_0 = ((w - 1) as f64 * s - x) / ((n - t) * d) as f64;
+        let threshold = ((((n - t) as f64) / t as f64).ln()
+            + d as f64 * ((1.0 - pi_0) / (1.0 - pi_1)).ln())
+            / ((pi_1 / pi_0).ln() + ((1.0 - pi_0) / (1.0 - pi_1)).ln());
+
+        private_key
+            .parity_check_blocks
+            .iter()
+            .enumerate()
+            .map(|(block_pos, block)| {
+                let min_threshold = (block.weight() + 1) >> 1;
+                let block_threshold = if threshold.is_finite() {
+                    ((threshold * block.weight() as f64 / d as f64).ceil() as usize)
+                        .max(min_threshold)
+                } else {
+                    min_threshold
+                };
+                let max_counter = unsatisfied[block_pos * r..(block_pos + 1) * r]
+                    .iter()
+                    .max()
+                    .copied()
+                    .unwrap_or(0);
+                block_threshold
+                    .max(max_counter.saturating_sub(self.max_counter_delta))
+                    .max(1)
+            })
+            .collect()
+    }
+}
+
+fn ln_binomial(n: usize, k: usize) -> f64 {
+    (0..k)
+        .map(|i| ((n - i) as f64).ln() - ((i + 1) as f64).ln())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, DecodingAlgorithm};
+    use crate::bit_vector::BitVector;
+    use crate::certificateless_qc_mdpc::test_node;
+    use crate::Params;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn test_decoders_round_trip() {
+        let params = Params::TEST;
+        let mut rng = ChaCha20Rng::seed_from_u64(4);
+        let (node, _) = test_node(1, &params, &mut rng);
+        let private_key = node.private_key();
+        let (public_key, _) = node.public_key_and_witness().unwrap();
+        for algorithm in [
+            DecodingAlgorithm::BitFlipping,
+            DecodingAlgorithm::BlackGrayFlip,
+            DecodingAlgorithm::ConstantTimeBitFlipping,
+        ] {
+            let zero_syndrome = BitVector::zeros(params.p);
+            assert_eq!(
+                algorithm.decode(&private_key, &zero_syndrome),
+                Ok(BitVector::zeros(params.n()))
+            );
+            for _ in 0..10 {
+                let error = public_key.random_error_vector_with_rng(&mut rng);
+                let syndrome = private_key.syndrome(&error);
+                assert_eq!(algorithm.decode(&private_key, &syndrome), Ok(error));
+            }
+        }
+    }
+
+    /// `cargo test --release -- --ignored --nocapture estimate_dfr`
+    #[test]
+    #[ignore]
+    fn estimate_dfr() {
+        const KEYS: usize = 4;
+        const ERRORS_PER_KEY: usize = 250;
+        let params = Params::P8053;
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        for algorithm in [DecodingAlgorithm::BitFlipping, DecodingAlgorithm::BlackGrayFlip] {
+            let mut failures = 0;
+            for node_id in 1..=KEYS {
+                let (node, _) = test_node(node_id, &params, &mut rng);
+                let private_key = node.private_key();
+                let (public_key, _) = node.public_key_and_witness().unwrap();
+                for _ in 0..ERRORS_PER_KEY {
+                    let error = public_key.random_error_vector_with_rng(&mut rng);
+                    let syndrome = private_key.syndrome(&error);
+                    if algorithm.decode(&private_key, &syndrome) != Ok(error) {
+                        failures += 1;
+                    }
+                }
+            }
+            println!(
+                "{:?}: {} failures out of {} decodings",
+                algorithm,
+                failures,
+                KEYS * ERRORS_PER_KEY
+            );
+        }
+    }
+}
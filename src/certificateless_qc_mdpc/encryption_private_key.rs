@@ -1,127 +1,482 @@
-use crate::binary_matrix_operations::matrix_is_zero;
-use crate::my_bool::MyBool;
-use nalgebra::DMatrix;
-use std::cmp::max;
+use crate::binary_matrix_operations::SparseCirculantMatrix;
+use crate::bit_vector::BitVector;
+use crate::certificateless_qc_mdpc::decoder::{Decoder, DecodingAlgorithm};
+use crate::certificateless_qc_mdpc::hybrid::{aead_decrypt, HybridCiphertext};
+use crate::certificateless_qc_mdpc::kem::{
+    error_vector_to_seed_mask, seed_to_error_vector, shared_secret, xor_secrets, KemCiphertext,
+    KEM_SECRET_SIZE,
+};
+use crate::certificateless_qc_mdpc::kobara_imai::kobara_imai_decode;
+use crate::certificateless_qc_mdpc::armor::{armor, dearmor, dearmor_expecting};
+use crate::certificateless_qc_mdpc::wire_format::{ObjectType, WireReader, WireWriter};
+use crate::{Error, Params};
+use std::fmt;
+use std::str::FromStr;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Parity-check matrix `[H_0 | H_1]`, each circulant block being stored as the positions of the
+/// ones in its first row.
+///
+/// The secrets are wiped on drop and `Debug` doesn't print them. Copies have to be made explicitly
+/// with [`Self::clone_secret`].
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "PrivateKeyFields"))]
 pub struct CertificatelessQcMdpcPrivateKey {
-    pub(super) parity_check_matrix: DMatrix<MyBool>,
+    pub(super) parity_check_blocks: Vec<SparseCirculantMatrix>,
     pub(super) expected_encoded_vector_size: usize,
+    pub(super) errors_count: usize,
+    /// Public bound on the weight of the blocks, see [`Self::syndrome`]
+    pub(super) padded_block_weight: usize,
+    pub(super) decoding_algorithm: DecodingAlgorithm,
+    /// Used to derive the shared secret when decapsulation fails
+    pub(super) implicit_rejection_secret: [u8; KEM_SECRET_SIZE],
 }
 
 impl CertificatelessQcMdpcPrivateKey {
-    pub fn decrypt(&self, data: &DMatrix<MyBool>) -> Result<Vec<u8>, &'static str> {
-        if data.ncols() != self.expected_encoded_vector_size {
-            return Err("Invalid data size");
-        }
-
-        let mut encoded_data = data.clone();
-        //let H = self.parity_check_matrix.clone();
-        let mut syn = self.parity_check_matrix.clone() * encoded_data.transpose();
-        //println!("{} {} {} {}", data.nrows(), data.ncols(), syn.nrows(), syn.ncols());
-        let limit = 10usize;
-        let delta = 5usize;
-        for _i in 0..limit {
-            let mut unsatisfied = vec![0usize; encoded_data.ncols()];
-            for j in 0..encoded_data.ncols() {
-                for k in 0..self.parity_check_matrix.nrows() {
-                    if **self.parity_check_matrix.get((k, j)).unwrap() && **syn.get((k, 0)).unwrap()
-                    {
-                        unsatisfied[j] += 1;
-                    }
-                }
-            }
-            let b = max(
-                (*unsatisfied.iter().max().unwrap() as i32) - delta as i32,
-                0,
-            ) as usize;
-            for j in 0..encoded_data.ncols() {
-                if unsatisfied[j] > b {
-                    **(encoded_data.get_mut((0, j)).unwrap()) ^= true;
-                    syn += self
-                        .parity_check_matrix
-                        .view_range(0..self.parity_check_matrix.nrows(), j..j + 1);
-                }
-            }
+    pub(super) fn new(
+        parity_check_blocks: Vec<SparseCirculantMatrix>,
+        params: &Params,
+        decoding_algorithm: DecodingAlgorithm,
+        implicit_rejection_secret: [u8; KEM_SECRET_SIZE],
+    ) -> Self {
+        let padded_block_weight = padded_block_weight(&parity_check_blocks, params);
+        Self {
+            parity_check_blocks,
+            expected_encoded_vector_size: params.n(),
+            errors_count: params.t,
+            padded_block_weight,
+            decoding_algorithm,
+            implicit_rejection_secret,
+        }
+    }
 
-            //println!("Round {}: {} unsatisfied, sum of syn = {} (is zero = {})", _i, *unsatisfied.iter().max().unwrap() as i32, matrix_elements_sum(&syn), matrix_is_zero(&syn));
+    pub fn decrypt(&self, data: &BitVector) -> Result<Vec<u8>, Error> {
+        self.decrypt_with(data, &self.decoding_algorithm)
+    }
 
-            if matrix_is_zero(&syn) {
-                let mut result = vec![0u8; encoded_data.ncols() << 3];
-                for col in 0..encoded_data.ncols() {
-                    result[col >> 3] |= (**encoded_data.get((0, col)).unwrap() as u8) << (col & 7);
-                }
-                return Ok(result);
-            }
+    pub fn decrypt_with<D: Decoder + ?Sized>(
+        &self,
+        data: &BitVector,
+        decoder: &D,
+    ) -> Result<Vec<u8>, Error> {
+        if data.len() != self.expected_encoded_vector_size {
+            return Err(Error::InvalidLength {
+                expected: self.expected_encoded_vector_size,
+                actual: data.len(),
+            });
         }
-        Err("Decoding failed")
+
+        let error = self.decrypt_syndrome_with(&self.syndrome(data), decoder)?;
+        // The generator matrix is systematic, the message word is the beginning of the codeword
+        let message_word = (data ^ &error).slice(0..self.block_size());
+        kobara_imai_decode(&message_word, &error, self.errors_count)
+    }
+
+    pub fn decrypt_syndrome(&self, syndrome: &BitVector) -> Result<BitVector, Error> {
+        self.decrypt_syndrome_with(syndrome, &self.decoding_algorithm)
     }
 
-    pub fn decrypt_syndrome(&self, syndrome: &DMatrix<MyBool>) -> Result<Vec<bool>, &'static str> {
-        let ncols = syndrome.nrows() << 1;
-        if ncols != self.expected_encoded_vector_size {
-            return Err("Invalid data size");
+    pub fn decrypt_syndrome_with<D: Decoder + ?Sized>(
+        &self,
+        syndrome: &BitVector,
+        decoder: &D,
+    ) -> Result<BitVector, Error> {
+        self.check_syndrome_length(syndrome)?;
+        decoder.decode(self, syndrome)
+    }
+
+    /// Inverse of `CertificatelessQcMdpcPublicKey::niederreiter_encrypt`, returns the error vector
+    pub fn niederreiter_decrypt(&self, syndrome: &BitVector) -> Result<BitVector, Error> {
+        self.check_syndrome_length(syndrome)?;
+        // The public parity-check matrix is H_1^-1 * H, so the private syndrome is H_1 * syndrome^T
+        let mut private_syndrome = BitVector::zeros(syndrome.len());
+        let last_block = &self.parity_check_blocks[self.parity_check_blocks.len() - 1];
+        for (index, is_one) in last_block.padded_first_row_ones(self.padded_block_weight) {
+            private_syndrome.conditional_xor_assign(&syndrome.rotate_left_constant_time(index), is_one);
+        }
+        let error_vector = self.decrypt_syndrome(&private_syndrome)?;
+        if error_vector.weight() != self.errors_count {
+            return Err(Error::DecodingFailure);
         }
+        Ok(error_vector)
+    }
 
-        let mut error = vec![false; ncols];
-        let mut syn = syndrome.clone();
-        let limit = 10usize;
-        let delta = 5usize;
-        for _i in 0..limit {
-            let mut unsatisfied = vec![0usize; ncols];
-            for j in 0..ncols {
-                for k in 0..self.parity_check_matrix.nrows() {
-                    if **self.parity_check_matrix.get((k, j)).unwrap() && **syn.get((k, 0)).unwrap()
-                    {
-                        unsatisfied[j] += 1;
-                    }
-                }
-            }
-            let b = (*unsatisfied.iter().max().unwrap()).abs_diff(delta); //max((*unsatisfied.iter().max().unwrap() as i32) - delta as i32, 0) as usize;
-            for j in 0..ncols {
-                if unsatisfied[j] > b {
-                    error[j] ^= true;
-                    syn += self
-                        .parity_check_matrix
-                        .view_range(0..self.parity_check_matrix.nrows(), j..j + 1);
-                }
-            }
+    /// Inverse of `CertificatelessQcMdpcPublicKey::encapsulate`.
+    ///
+    /// Invalid ciphertexts and decoding failures are rejected implicitly: the returned secret is
+    /// then derived from the implicit rejection secret, so it looks random and no error is reported.
+    pub fn decapsulate(
+        &self,
+        ciphertext: &KemCiphertext,
+    ) -> Result<[u8; KEM_SECRET_SIZE], Error> {
+        self.check_syndrome_length(&ciphertext.syndrome)?;
+        let error_vector = self
+            .niederreiter_decrypt(&ciphertext.syndrome)
+            .unwrap_or_else(|_| BitVector::zeros(self.expected_encoded_vector_size));
+        let seed = xor_secrets(
+            &ciphertext.masked_seed,
+            &error_vector_to_seed_mask(&error_vector),
+        );
+        let expected_error_vector =
+            seed_to_error_vector(&seed, self.expected_encoded_vector_size, self.errors_count);
+        let mask = 0u8.wrapping_sub(expected_error_vector.constant_time_eq(&error_vector) as u8);
+        let mut selected_seed = [0u8; KEM_SECRET_SIZE];
+        for (selected, (valid, rejection)) in selected_seed
+            .iter_mut()
+            .zip(seed.iter().zip(self.implicit_rejection_secret.iter()))
+        {
+            *selected = rejection ^ ((valid ^ rejection) & mask);
+        }
+        Ok(shared_secret(&selected_seed, ciphertext))
+    }
 
-            //println!("Round {}: {} unsatisfied, sum of syn = {} (is zero = {})", _i, *unsatisfied.iter().max().unwrap() as i32, matrix_elements_sum(&syn), matrix_is_zero(&syn));
+    /// Inverse of `CertificatelessQcMdpcPublicKey::hybrid_encrypt`
+    pub fn hybrid_decrypt(
+        &self,
+        ciphertext: &HybridCiphertext,
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let shared_secret = self.decapsulate(&ciphertext.kem_ciphertext)?;
+        aead_decrypt(&shared_secret, &ciphertext.aead_ciphertext, associated_data)
+    }
 
-            if matrix_is_zero(&syn) {
-                return Ok(error);
-            }
+    /// `H_0 first row || H_1 first row || decoding algorithm (1 byte) || implicit rejection
+    /// secret`, after the wire format header. `params` must be the parameter set the key was
+    /// generated with.
+    pub fn to_bytes(&self, params: &Params) -> Vec<u8> {
+        let mut writer = WireWriter::new(ObjectType::PrivateKey, params);
+        for block in &self.parity_check_blocks {
+            writer.write_bits(&block.first_row());
+        }
+        writer.write_bytes(&[match self.decoding_algorithm {
+            DecodingAlgorithm::BitFlipping => 0,
+            DecodingAlgorithm::BlackGrayFlip => 1,
+            DecodingAlgorithm::ConstantTimeBitFlipping => 2,
+        }]);
+        writer.write_bytes(&self.implicit_rejection_secret);
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params) -> Result<Self, Error> {
+        let mut reader = WireReader::new(bytes, ObjectType::PrivateKey, params)?;
+        let parity_check_blocks = (0..params.n_0)
+            .map(|_| Ok(SparseCirculantMatrix::from_first_row(&reader.read_bits(params.p)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let decoding_algorithm = match reader.read_array::<1>()? {
+            [0] => DecodingAlgorithm::BitFlipping,
+            [1] => DecodingAlgorithm::BlackGrayFlip,
+            [2] => DecodingAlgorithm::ConstantTimeBitFlipping,
+            _ => return Err(Error::InvalidEncoding("unknown decoding algorithm")),
+        };
+        let implicit_rejection_secret = reader.read_array()?;
+        reader.finish()?;
+        Ok(Self::new(
+            parity_check_blocks,
+            params,
+            decoding_algorithm,
+            implicit_rejection_secret,
+        ))
+    }
+
+    /// PEM-like text encoding of [`Self::to_bytes`], parsed back with `FromStr`
+    pub fn to_armored(&self, params: &Params) -> String {
+        armor(ARMOR_LABEL, params, &self.to_bytes(params))
+    }
+
+    /// Parses the output of [`Self::to_armored`], failing unless it was written with `params`.
+    /// `FromStr` trusts the parameter set written in the text instead.
+    pub fn from_armored(text: &str, params: &Params) -> Result<Self, Error> {
+        let bytes = dearmor_expecting(text, ARMOR_LABEL, ObjectType::PrivateKey, params)?;
+        Self::from_bytes(&bytes, params)
+    }
+
+    /// Explicit copy of the key
+    pub fn clone_secret(&self) -> Self {
+        Self {
+            parity_check_blocks: self.parity_check_blocks.clone(),
+            expected_encoded_vector_size: self.expected_encoded_vector_size,
+            errors_count: self.errors_count,
+            padded_block_weight: self.padded_block_weight,
+            decoding_algorithm: self.decoding_algorithm,
+            implicit_rejection_secret: self.implicit_rejection_secret,
         }
-        Err("Decoding failed")
     }
 
     pub fn weight(&self) -> usize {
-        self.parity_check_matrix
-            .row(0)
+        self.parity_check_blocks
+            .iter()
+            .map(|block| block.weight())
+            .sum()
+    }
+
+    pub fn decoding_algorithm(&self) -> DecodingAlgorithm {
+        self.decoding_algorithm
+    }
+
+    pub fn set_decoding_algorithm(&mut self, decoding_algorithm: DecodingAlgorithm) {
+        self.decoding_algorithm = decoding_algorithm;
+    }
+
+    pub fn first_line(&self) -> BitVector {
+        self.parity_check_blocks
             .iter()
-            .filter(|b| ***b)
-            .count()
+            .fold(BitVector::zeros(0), |line, block| line.concat(&block.first_row()))
+    }
+
+    pub fn errors_count(&self) -> usize {
+        self.errors_count
+    }
+
+    /// Length of the codewords, which is also the number of columns of the parity-check matrix
+    pub fn encoded_vector_size(&self) -> usize {
+        self.expected_encoded_vector_size
+    }
+
+    fn check_syndrome_length(&self, syndrome: &BitVector) -> Result<(), Error> {
+        if syndrome.len() != self.block_size() {
+            return Err(Error::InvalidLength {
+                expected: self.block_size(),
+                actual: syndrome.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn block_size(&self) -> usize {
+        self.parity_check_blocks[0].size()
+    }
+
+    /// Parity-check equations (syndrome positions) involving column `column`
+    fn column_ones(&self, column: usize) -> impl Iterator<Item = usize> + '_ {
+        let block_size = self.block_size();
+        self.parity_check_blocks[column / block_size].column_ones(column % block_size)
+    }
+
+    /// Computed with rotations of `vector`, the running time doesn't depend on the key: each block
+    /// is processed as if it had the maximum weight allowed by the parameters, the rotations for
+    /// the missing ones being discarded
+    pub fn syndrome(&self, vector: &BitVector) -> BitVector {
+        let block_size = self.block_size();
+        let mut syndrome = BitVector::zeros(block_size);
+        for (block_pos, block) in self.parity_check_blocks.iter().enumerate() {
+            let vector_block = vector.slice(block_pos * block_size..(block_pos + 1) * block_size);
+            for (index, is_one) in block.padded_first_row_ones(self.padded_block_weight) {
+                syndrome.conditional_xor_assign(&vector_block.rotate_left_constant_time(index), is_one);
+            }
+        }
+        syndrome
+    }
+
+    /// For each column, number of unsatisfied parity-check equations involving it
+    pub fn unsatisfied_parity_checks(&self, syndrome: &BitVector) -> Vec<usize> {
+        (0..self.expected_encoded_vector_size)
+            .map(|column| {
+                self.column_ones(column)
+                    .filter(|row| syndrome.get(*row))
+                    .count()
+            })
+            .collect()
+    }
+
+    /// Updates the syndrome after flipping bit `column` of the error vector
+    pub fn flip_syndrome_column(&self, syndrome: &mut BitVector, column: usize) {
+        for row in self.column_ones(column) {
+            syndrome.flip(row);
+        }
     }
+}
+
+const ARMOR_LABEL: &str = "PRIVATE KEY";
 
-    pub fn first_line(&self) -> Vec<MyBool> {
-        self.parity_check_matrix.row(0).iter().cloned().collect()
+/// [`Params::max_block_weight`]. Only a malformed key can have heavier blocks, it then gets padded
+/// to its own weight.
+fn padded_block_weight(parity_check_blocks: &[SparseCirculantMatrix], params: &Params) -> usize {
+    parity_check_blocks
+        .iter()
+        .map(SparseCirculantMatrix::weight)
+        .fold(params.max_block_weight(), usize::max)
+}
+
+/// Deserialized form of [`CertificatelessQcMdpcPrivateKey`], whose sizes are checked against each
+/// other the way [`CertificatelessQcMdpcPrivateKey::from_bytes`] derives them from `Params`. Its
+/// secrets are wiped when it is dropped.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "CertificatelessQcMdpcPrivateKey")]
+struct PrivateKeyFields {
+    parity_check_blocks: Vec<SparseCirculantMatrix>,
+    expected_encoded_vector_size: usize,
+    errors_count: usize,
+    padded_block_weight: usize,
+    decoding_algorithm: DecodingAlgorithm,
+    implicit_rejection_secret: [u8; KEM_SECRET_SIZE],
+}
+
+#[cfg(feature = "serde")]
+impl Drop for PrivateKeyFields {
+    fn drop(&mut self) {
+        self.parity_check_blocks.zeroize();
+        self.implicit_rejection_secret.zeroize();
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<PrivateKeyFields> for CertificatelessQcMdpcPrivateKey {
+    type Error = Error;
+
+    fn try_from(mut fields: PrivateKeyFields) -> Result<Self, Self::Error> {
+        let [left_block, right_block] = fields.parity_check_blocks.as_slice() else {
+            return Err(Error::InvalidEncoding("the parity-check matrix must have two blocks"));
+        };
+        let p = left_block.size();
+        if right_block.size() != p
+            || p < crate::certificateless_qc_mdpc::KOBARA_IMAI_MIN_MESSAGE_WORD_SIZE
+            || p.checked_mul(2) != Some(fields.expected_encoded_vector_size)
+        {
+            return Err(Error::InvalidEncoding("inconsistent private key sizes"));
+        }
+        if fields.errors_count == 0 || fields.errors_count > fields.expected_encoded_vector_size {
+            return Err(Error::InvalidEncoding("errors count must be between 1 and n"));
+        }
+        // It sets the number of rotations of the constant-time decoder, so it isn't trusted. The
+        // left block is h_i_3, of weight w / 2, which is all the block weights depend on.
+        let params = Params {
+            w: left_block.weight() << 1,
+            ..Params::default()
+        };
+        if fields.padded_block_weight != padded_block_weight(&fields.parity_check_blocks, &params) {
+            return Err(Error::InvalidEncoding("padded block weight doesn't match the blocks"));
+        }
+        Ok(Self {
+            parity_check_blocks: std::mem::take(&mut fields.parity_check_blocks),
+            expected_encoded_vector_size: fields.expected_encoded_vector_size,
+            errors_count: fields.errors_count,
+            padded_block_weight: fields.padded_block_weight,
+            decoding_algorithm: fields.decoding_algorithm,
+            implicit_rejection_secret: fields.implicit_rejection_secret,
+        })
     }
 }
 
-impl ToString for CertificatelessQcMdpcPrivateKey {
-    fn to_string(&self) -> String {
-        let mut s = String::new();
-        for i in 0..self.parity_check_matrix.nrows() {
-            for j in 0..self.parity_check_matrix.ncols() {
-                s.push(if **self.parity_check_matrix.get((i, j)).unwrap() {
-                    '1'
-                } else {
-                    '0'
-                });
+impl fmt::Debug for CertificatelessQcMdpcPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertificatelessQcMdpcPrivateKey")
+            .field("encoded_vector_size", &self.expected_encoded_vector_size)
+            .field("errors_count", &self.errors_count)
+            .field("decoding_algorithm", &self.decoding_algorithm)
+            .field("secrets", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Drop for CertificatelessQcMdpcPrivateKey {
+    fn drop(&mut self) {
+        self.parity_check_blocks
+            .iter_mut()
+            .for_each(Zeroize::zeroize);
+        self.implicit_rejection_secret.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for CertificatelessQcMdpcPrivateKey {}
+
+impl FromStr for CertificatelessQcMdpcPrivateKey {
+    type Err = Error;
+
+    /// Parses the output of [`CertificatelessQcMdpcPrivateKey::to_armored`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (params, bytes) = dearmor(s, ARMOR_LABEL)?;
+        Self::from_bytes(&bytes, &params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::certificateless_qc_mdpc::{test_key_pair, KemCiphertext};
+    use crate::{Error, Params};
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn test_niederreiter_round_trip() {
+        let mut rng = ChaCha20Rng::seed_from_u64(8);
+        let (private_key, public_key) = test_key_pair(&mut rng);
+        for _ in 0..5 {
+            let error_vector = public_key.random_error_vector_with_rng(&mut rng);
+            let syndrome = public_key.niederreiter_encrypt(&error_vector).unwrap();
+            assert_eq!(private_key.niederreiter_decrypt(&syndrome), Ok(error_vector));
+        }
+    }
+
+    #[test]
+    fn test_kem_round_trip_and_implicit_rejection() {
+        let params = Params::TEST;
+        let mut rng = ChaCha20Rng::seed_from_u64(9);
+        let (private_key, public_key) = test_key_pair(&mut rng);
+        let (ciphertext, secret) = public_key.encapsulate_with_rng(&mut rng);
+        assert_eq!(private_key.decapsulate(&ciphertext), Ok(secret));
+
+        let mut tampered_seed = ciphertext.clone();
+        tampered_seed.masked_seed[0] ^= 1;
+        let mut tampered_syndrome = ciphertext.clone();
+        tampered_syndrome.syndrome.flip(0);
+        for tampered in [&tampered_seed, &tampered_syndrome] {
+            let rejected = private_key.decapsulate(tampered).unwrap();
+            assert_ne!(rejected, secret);
+            // Rejection is deterministic, so that retrying doesn't tell it apart from a success
+            assert_eq!(private_key.decapsulate(tampered), Ok(rejected));
+        }
+
+        let short = KemCiphertext {
+            syndrome: ciphertext.syndrome.slice(0..params.p - 1),
+            masked_seed: ciphertext.masked_seed,
+        };
+        assert!(matches!(
+            private_key.decapsulate(&short),
+            Err(Error::InvalidLength { .. })
+        ));
+    }
+
+    #[test]
+    fn test_hybrid_round_trip_and_tampered_associated_data() {
+        let mut rng = ChaCha20Rng::seed_from_u64(10);
+        let (private_key, public_key) = test_key_pair(&mut rng);
+        let ciphertext = public_key.hybrid_encrypt_with_rng(b"hybrid message", b"header", &mut rng);
+        assert_eq!(
+            private_key.hybrid_decrypt(&ciphertext, b"header"),
+            Ok(b"hybrid message".to_vec())
+        );
+        assert_eq!(
+            private_key.hybrid_decrypt(&ciphertext, b"headers"),
+            Err(Error::DecryptionFailure)
+        );
+        let mut tampered = ciphertext.clone();
+        tampered.aead_ciphertext[0] ^= 1;
+        assert_eq!(
+            private_key.hybrid_decrypt(&tampered, b"header"),
+            Err(Error::DecryptionFailure)
+        );
+    }
+
+    #[test]
+    fn test_kobara_imai_round_trip_and_tampered_ciphertext() {
+        let params = Params::TEST;
+        let mut rng = ChaCha20Rng::seed_from_u64(11);
+        let (private_key, public_key) = test_key_pair(&mut rng);
+        for message in [&b""[..], b"kobara-imai", &vec![0x5a; public_key.max_message_size()]] {
+            let ciphertext = public_key.encrypt_with_rng(message, &mut rng).unwrap();
+            assert_eq!(private_key.decrypt(&ciphertext), Ok(message.to_vec()));
+            for position in [0, params.p + 1] {
+                let mut tampered = ciphertext.clone();
+                tampered.flip(position);
+                assert!(private_key.decrypt(&tampered).is_err());
             }
-            s.push('\n');
         }
-        s
+        let too_long = vec![0; public_key.max_message_size() + 1];
+        assert!(matches!(
+            public_key.encrypt_with_rng(&too_long, &mut rng),
+            Err(Error::MessageTooLong { .. })
+        ));
     }
 }
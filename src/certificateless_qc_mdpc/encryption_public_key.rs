@@ -1,90 +1,324 @@
-use crate::binary_matrix_operations::{
-    concat_horizontally_mat, make_circulant_matrix, make_identity_matrix,
+use crate::bit_vector::BitVector;
+use crate::certificateless_qc_mdpc::hybrid::{aead_encrypt, HybridCiphertext};
+use crate::certificateless_qc_mdpc::kem::{
+    error_vector_to_seed_mask, seed_to_error_vector, shared_secret, xor_secrets, KemCiphertext,
+    KEM_SECRET_SIZE,
 };
+use crate::certificateless_qc_mdpc::kobara_imai::{
+    kobara_imai_encode, kobara_imai_framed_message_size, KOBARA_IMAI_SEED_SIZE,
+};
+use crate::certificateless_qc_mdpc::armor::{armor, dearmor, dearmor_expecting};
+use crate::certificateless_qc_mdpc::wire_format::{ObjectType, WireReader, WireWriter};
 use crate::certificateless_qc_mdpc::{
-    generate_hash_id_vector_correct_weight, NodeWitnessSigPubKey,
+    derive_invertible_s_i, generate_invertible_hash_id_vector, NodeWitnessSigPubKey,
 };
-use crate::my_bool::MyBool;
-use crate::N_0;
-use nalgebra::DMatrix;
-use num::integer::Roots;
+use crate::{Error, Params};
+use std::str::FromStr;
 use rand::Rng;
 use rand_chacha::ChaCha20Rng;
-use rand_core::SeedableRng;
-use std::cmp::min;
-use crate::utils::{multiply_2_matrix_first_line_vector, try_invert_matrix_vector};
+use rand_core::{CryptoRng, RngCore, SeedableRng};
+use crate::utils::{
+    multiply_2_matrix_first_line_vector, transpose_matrix_first_line_vector,
+    try_invert_matrix_vector, MESSAGE_LENGTH_PREFIX_SIZE,
+};
 
+/// The generator matrix is systematic, `[I | C^T]`, with `C^T` circulant: only its first line is stored
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "PublicKeyFields"))]
 pub struct CertificatelessQcMdpcPublicKey {
-    pub(super) generator_matrix: DMatrix<MyBool>,
+    pub(super) generator_first_line: BitVector,
     pub(super) max_message_size_bits: usize,
+    pub(super) encoded_vector_size: usize,
     pub(super) errors_count: usize,
 }
 
 impl CertificatelessQcMdpcPublicKey {
-    #[allow(non_snake_case)]
-    pub fn encrypt(&self, data: &[u8]) -> DMatrix<MyBool> { // TODO: until decryption works
-        assert!(data.len() << 3 <= self.max_message_size_bits);
-        let mut message = DMatrix::from_element(1, self.max_message_size_bits, MyBool::from(false));
-        for i in 0..min(self.max_message_size_bits, data.len() << 3) {
-            // wrong check
-            message[(0, i)] = MyBool::from(data[i >> 3] & (1 << (i & 7)) != 0);
+    pub fn encrypt(&self, data: &[u8]) -> Result<BitVector, Error> {
+        self.encrypt_with_rng(data, &mut ChaCha20Rng::from_entropy())
+    }
+
+    /// Same as [`Self::encrypt`], drawing the randomness from `rng`
+    pub fn encrypt_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        data: &[u8],
+        rng: &mut R,
+    ) -> Result<BitVector, Error> {
+        if data.len() > self.max_message_size() {
+            return Err(Error::MessageTooLong {
+                max: self.max_message_size(),
+                actual: data.len(),
+            });
+        }
+        let mut seed = [0u8; KOBARA_IMAI_SEED_SIZE];
+        rng.fill_bytes(&mut seed);
+        let (message, e) = kobara_imai_encode(
+            data,
+            &seed,
+            self.max_message_size_bits,
+            self.encoded_vector_size,
+            self.errors_count,
+        );
+        // message * C^T, as a polynomial product
+        let redundancy = multiply_2_matrix_first_line_vector(&message, &self.generator_first_line);
+        Ok(&message.concat(&redundancy) ^ &e)
+    }
+
+    /// Maximum size in bytes of the data passed to [`Self::encrypt`]
+    pub fn max_message_size(&self) -> usize {
+        (kobara_imai_framed_message_size(self.max_message_size_bits) >> 3)
+            - MESSAGE_LENGTH_PREFIX_SIZE
+    }
+
+    /// Niederreiter encryption: returns the syndrome of `error_vector`, of weight `errors_count`,
+    /// relatively to the systematic public parity-check matrix `[C | I]`
+    pub fn niederreiter_encrypt(&self, error_vector: &BitVector) -> Result<BitVector, Error> {
+        if error_vector.len() != self.encoded_vector_size {
+            return Err(Error::InvalidLength {
+                expected: self.encoded_vector_size,
+                actual: error_vector.len(),
+            });
+        }
+        if error_vector.weight() != self.errors_count {
+            return Err(Error::InvalidWeight {
+                expected: self.errors_count,
+                actual: error_vector.weight(),
+            });
+        }
+        Ok(self.syndrome(error_vector))
+    }
+
+    fn syndrome(&self, error_vector: &BitVector) -> BitVector {
+        let p = self.max_message_size_bits;
+        // C * e_0^T = e_0 * C^T
+        &multiply_2_matrix_first_line_vector(&error_vector.slice(0..p), &self.generator_first_line)
+            ^ &error_vector.slice(p..2 * p)
+    }
+
+    /// Random vector of length `n` and weight `errors_count`, to be used with [`Self::niederreiter_encrypt`]
+    pub fn random_error_vector(&self) -> BitVector {
+        self.random_error_vector_with_rng(&mut ChaCha20Rng::from_entropy())
+    }
+
+    pub fn random_error_vector_with_rng<R: RngCore + CryptoRng>(&self, rng: &mut R) -> BitVector {
+        let n = self.encoded_vector_size;
+        let mut error_vector = BitVector::zeros(n);
+        let mut weight = 0usize;
+        while weight < self.errors_count {
+            let idx = rng.gen_range(0..n);
+            if !error_vector.get(idx) {
+                error_vector.set(idx, true);
+                weight += 1;
+            }
         }
-        let G = self.generator_matrix.clone();
-        let e = self.get_error_vector();
-        (message * G) + e
+        error_vector
+    }
+
+    /// Key encapsulation, IND-CCA2 through the FO⊥ transform as in BIKE: returns the ciphertext
+    /// and the shared secret
+    pub fn encapsulate(&self) -> (KemCiphertext, [u8; KEM_SECRET_SIZE]) {
+        self.encapsulate_with_rng(&mut ChaCha20Rng::from_entropy())
+    }
+
+    /// Same as [`Self::encapsulate`], drawing the randomness from `rng`
+    pub fn encapsulate_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> (KemCiphertext, [u8; KEM_SECRET_SIZE]) {
+        let mut seed = [0u8; KEM_SECRET_SIZE];
+        rng.fill_bytes(&mut seed);
+        let error_vector = seed_to_error_vector(
+            &seed,
+            self.encoded_vector_size,
+            self.errors_count,
+        );
+        let ciphertext = KemCiphertext {
+            syndrome: self.syndrome(&error_vector),
+            masked_seed: xor_secrets(&seed, &error_vector_to_seed_mask(&error_vector)),
+        };
+        let secret = shared_secret(&seed, &ciphertext);
+        (ciphertext, secret)
+    }
+
+    /// Encrypts a message of any length: a key encapsulated with [`Self::encapsulate`] is used
+    /// to encrypt it with ChaCha20-Poly1305, authenticating `associated_data` as well
+    pub fn hybrid_encrypt(&self, data: &[u8], associated_data: &[u8]) -> HybridCiphertext {
+        self.hybrid_encrypt_with_rng(data, associated_data, &mut ChaCha20Rng::from_entropy())
+    }
+
+    /// Same as [`Self::hybrid_encrypt`], drawing the randomness from `rng`
+    pub fn hybrid_encrypt_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        data: &[u8],
+        associated_data: &[u8],
+        rng: &mut R,
+    ) -> HybridCiphertext {
+        let (kem_ciphertext, shared_secret) = self.encapsulate_with_rng(rng);
+        HybridCiphertext {
+            kem_ciphertext,
+            aead_ciphertext: aead_encrypt(&shared_secret, data, associated_data),
+        }
+    }
+
+    /// Generator first line, after the wire format header. `params` must be the parameter set the
+    /// key was generated with.
+    pub fn to_bytes(&self, params: &Params) -> Vec<u8> {
+        let mut writer = WireWriter::new(ObjectType::PublicKey, params);
+        writer.write_bits(&self.generator_first_line);
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params) -> Result<Self, Error> {
+        let mut reader = WireReader::new(bytes, ObjectType::PublicKey, params)?;
+        let generator_first_line = reader.read_bits(params.p)?;
+        reader.finish()?;
+        Ok(Self {
+            generator_first_line,
+            max_message_size_bits: params.p,
+            encoded_vector_size: params.n(),
+            errors_count: params.t,
+        })
+    }
+
+    /// PEM-like text encoding of [`Self::to_bytes`], parsed back with `FromStr`
+    pub fn to_armored(&self, params: &Params) -> String {
+        armor(ARMOR_LABEL, params, &self.to_bytes(params))
+    }
+
+    /// Parses the output of [`Self::to_armored`], failing unless it was written with `params`.
+    /// `FromStr` trusts the parameter set written in the text instead.
+    pub fn from_armored(text: &str, params: &Params) -> Result<Self, Error> {
+        let bytes = dearmor_expecting(text, ARMOR_LABEL, ObjectType::PublicKey, params)?;
+        Self::from_bytes(&bytes, params)
     }
 
-    #[allow(non_snake_case)]
     pub fn check_is_valid(
         &self,
         node_id: usize,
-        s_i: &[MyBool],
+        s_i: &BitVector,
         witness: &NodeWitnessSigPubKey,
-        weight: usize,
+        params: &Params,
     ) -> bool {
-        let r_i: &[MyBool] = witness.pubkey_witness_vector.as_ref();
-        if self.max_message_size_bits != s_i.len() || self.max_message_size_bits != r_i.len() {
-            return false;
+        self.verify(node_id, s_i, witness, params).is_ok()
+    }
+
+    /// Same as [`Self::check_is_valid`], telling why the public key is rejected
+    pub fn verify(
+        &self,
+        node_id: usize,
+        s_i: &BitVector,
+        witness: &NodeWitnessSigPubKey,
+        params: &Params,
+    ) -> Result<(), Error> {
+        let r_i = &witness.pubkey_witness_vector;
+        if self.max_message_size_bits != params.p
+            || self.encoded_vector_size != params.n()
+            || self.errors_count != params.t
+        {
+            return Err(Error::ParametersMismatch);
         }
-        let s_i_inv = match try_invert_matrix_vector(s_i) {
-            None => return false,
-            Some(inverse_matrix) => inverse_matrix,
-        };
-        let h_i_1_weight = (weight >> 1).nth_root(3);
-        let h_i_1 = generate_hash_id_vector_correct_weight(
+        witness.verify(params)?;
+        for vector in [s_i, r_i] {
+            if vector.len() != self.max_message_size_bits {
+                return Err(Error::InvalidLength {
+                    expected: self.max_message_size_bits,
+                    actual: vector.len(),
+                });
+            }
+        }
+        // Same fallbacks as CertificatelessQcMdpc::from_seed for non-invertible vectors
+        let s_i = derive_invertible_s_i(s_i, params.s_i_weight())?;
+        let s_i_inv = try_invert_matrix_vector(&s_i).ok_or(Error::NonInvertibleVector)?;
+        let h_i_1_weight = params.h_i_1_weight();
+        let h_i_1 = generate_invertible_hash_id_vector(
             node_id,
             self.max_message_size_bits,
             h_i_1_weight,
-        );
-        let h_i_1_inv = match try_invert_matrix_vector(&h_i_1) {
-            None => return false,
-            Some(inverse_matrix) => inverse_matrix,
-        };
+        )?;
+        let h_i_1_inv = try_invert_matrix_vector(&h_i_1).ok_or(Error::NonInvertibleVector)?;
 
-        let G_i_verif_right_part = make_circulant_matrix(
+        let generator_first_line_verif = transpose_matrix_first_line_vector(
             &multiply_2_matrix_first_line_vector(&multiply_2_matrix_first_line_vector(&s_i_inv, &h_i_1_inv), r_i),
-            self.max_message_size_bits,
-            self.max_message_size_bits,
-            1,
-        ).transpose();
-        let mut G_i_verif = make_identity_matrix(self.max_message_size_bits);
-        concat_horizontally_mat(&mut G_i_verif, &G_i_verif_right_part);
-        G_i_verif == self.generator_matrix
+        );
+        if generator_first_line_verif != self.generator_first_line {
+            return Err(Error::InvalidWitness);
+        }
+        Ok(())
     }
+}
 
-    fn get_error_vector(&self) -> DMatrix<MyBool> {
-        let n = self.max_message_size_bits * N_0;
-        let mut rng = ChaCha20Rng::from_entropy();
-        let mut error_vector = DMatrix::from_element(1, n, MyBool::from(false));
-        let mut weight = 0usize;
-        while weight < self.errors_count {
-            let idx = rng.gen_range(0..n);
-            if !*error_vector[(0, idx)] {
-                error_vector[(0, idx)] = MyBool::from(true);
-                weight += 1;
-            }
+const ARMOR_LABEL: &str = "PUBLIC KEY";
+
+/// Deserialized form of [`CertificatelessQcMdpcPublicKey`], whose sizes are checked against each
+/// other the way [`CertificatelessQcMdpcPublicKey::from_bytes`] derives them from `Params`
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "CertificatelessQcMdpcPublicKey")]
+struct PublicKeyFields {
+    generator_first_line: BitVector,
+    max_message_size_bits: usize,
+    encoded_vector_size: usize,
+    errors_count: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<PublicKeyFields> for CertificatelessQcMdpcPublicKey {
+    type Error = Error;
+
+    fn try_from(fields: PublicKeyFields) -> Result<Self, Self::Error> {
+        let p = fields.max_message_size_bits;
+        if fields.generator_first_line.len() != p {
+            return Err(Error::InvalidLength {
+                expected: p,
+                actual: fields.generator_first_line.len(),
+            });
         }
-        error_vector
+        if p < crate::certificateless_qc_mdpc::KOBARA_IMAI_MIN_MESSAGE_WORD_SIZE
+            || p.checked_mul(2) != Some(fields.encoded_vector_size)
+        {
+            return Err(Error::InvalidEncoding("inconsistent public key sizes"));
+        }
+        if fields.errors_count == 0 || fields.errors_count > fields.encoded_vector_size {
+            return Err(Error::InvalidEncoding("errors count must be between 1 and n"));
+        }
+        Ok(Self {
+            generator_first_line: fields.generator_first_line,
+            max_message_size_bits: p,
+            encoded_vector_size: fields.encoded_vector_size,
+            errors_count: fields.errors_count,
+        })
+    }
+}
+
+impl FromStr for CertificatelessQcMdpcPublicKey {
+    type Err = Error;
+
+    /// Parses the output of [`CertificatelessQcMdpcPublicKey::to_armored`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (params, bytes) = dearmor(s, ARMOR_LABEL)?;
+        Self::from_bytes(&bytes, &params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::certificateless_qc_mdpc::test_key_pair;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn test_seeded_encryption_is_deterministic() {
+        let (_, public_key) = test_key_pair(&mut ChaCha20Rng::seed_from_u64(12));
+        let encrypt = |seed| {
+            public_key
+                .encrypt_with_rng(b"deterministic", &mut ChaCha20Rng::seed_from_u64(seed))
+                .unwrap()
+        };
+        let encapsulate =
+            |seed| public_key.encapsulate_with_rng(&mut ChaCha20Rng::seed_from_u64(seed));
+        assert_eq!(encrypt(1), encrypt(1));
+        assert_ne!(encrypt(1), encrypt(2));
+        assert_eq!(encapsulate(1), encapsulate(1));
+        assert_ne!(encapsulate(1), encapsulate(2));
     }
 }
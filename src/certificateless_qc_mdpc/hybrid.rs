@@ -0,0 +1,88 @@
+use crate::certificateless_qc_mdpc::kem::{KemCiphertext, KEM_SECRET_SIZE};
+use crate::certificateless_qc_mdpc::wire_format::{ObjectType, WireReader, WireWriter};
+use crate::{Error, Params};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+const AEAD_TAG_SIZE: usize = 16;
+
+/// Message encrypted with ChaCha20-Poly1305, under a key encapsulated with
+/// `CertificatelessQcMdpcPublicKey::encapsulate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HybridCiphertext {
+    pub kem_ciphertext: KemCiphertext,
+    pub aead_ciphertext: Vec<u8>,
+}
+
+impl HybridCiphertext {
+    /// `syndrome || masked seed || AEAD ciphertext and tag`, after the wire format header
+    pub fn to_bytes(&self, params: &Params) -> Vec<u8> {
+        let mut writer = WireWriter::new(ObjectType::HybridCiphertext, params);
+        writer.write_bits(&self.kem_ciphertext.syndrome);
+        writer.write_bytes(&self.kem_ciphertext.masked_seed);
+        writer.write_bytes(&self.aead_ciphertext);
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params) -> Result<Self, Error> {
+        let mut reader = WireReader::new(bytes, ObjectType::HybridCiphertext, params)?;
+        let kem_ciphertext = KemCiphertext {
+            syndrome: reader.read_bits(params.p)?,
+            masked_seed: reader.read_array()?,
+        };
+        let aead_ciphertext = reader.read_remaining();
+        if aead_ciphertext.len() < AEAD_TAG_SIZE {
+            return Err(Error::InvalidLength {
+                expected: bytes.len() - aead_ciphertext.len() + AEAD_TAG_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        Ok(Self {
+            kem_ciphertext,
+            aead_ciphertext: aead_ciphertext.to_vec(),
+        })
+    }
+}
+
+/// The key is only used once, so the nonce can be constant
+fn aead_cipher(shared_secret: &[u8; KEM_SECRET_SIZE]) -> (ChaCha20Poly1305, Nonce) {
+    (
+        ChaCha20Poly1305::new(Key::from_slice(shared_secret)),
+        Nonce::default(),
+    )
+}
+
+pub(super) fn aead_encrypt(
+    shared_secret: &[u8; KEM_SECRET_SIZE],
+    plaintext: &[u8],
+    associated_data: &[u8],
+) -> Vec<u8> {
+    let (cipher, nonce) = aead_cipher(shared_secret);
+    cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: associated_data,
+            },
+        )
+        .expect("ChaCha20-Poly1305 encryption cannot fail")
+}
+
+pub(super) fn aead_decrypt(
+    shared_secret: &[u8; KEM_SECRET_SIZE],
+    ciphertext: &[u8],
+    associated_data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let (cipher, nonce) = aead_cipher(shared_secret);
+    cipher
+        .decrypt(
+            &nonce,
+            Payload {
+                msg: ciphertext,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| Error::DecryptionFailure)
+}
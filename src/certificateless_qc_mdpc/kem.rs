@@ -0,0 +1,81 @@
+use crate::bit_vector::BitVector;
+use crate::certificateless_qc_mdpc::utils::generate_seeded_weight_vector;
+use crate::certificateless_qc_mdpc::wire_format::{ObjectType, WireReader, WireWriter};
+use crate::{Error, Params};
+use sha3::{Digest, Sha3_256};
+
+/// Size in bytes of the shared secret, of the encapsulated seed and of the implicit rejection secret
+pub const KEM_SECRET_SIZE: usize = 32;
+
+const ERROR_VECTOR_DOMAIN: u8 = b'H';
+const SEED_MASK_DOMAIN: u8 = b'L';
+const SHARED_SECRET_DOMAIN: u8 = b'K';
+
+/// Output of `CertificatelessQcMdpcPublicKey::encapsulate`: the Niederreiter syndrome of the
+/// error vector derived from the seed, and the seed masked by a hash of the error vector
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KemCiphertext {
+    pub syndrome: BitVector,
+    pub masked_seed: [u8; KEM_SECRET_SIZE],
+}
+
+impl KemCiphertext {
+    /// `syndrome || masked_seed`, after the wire format header
+    pub fn to_bytes(&self, params: &Params) -> Vec<u8> {
+        let mut writer = WireWriter::new(ObjectType::KemCiphertext, params);
+        writer.write_bits(&self.syndrome);
+        writer.write_bytes(&self.masked_seed);
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params) -> Result<Self, Error> {
+        let mut reader = WireReader::new(bytes, ObjectType::KemCiphertext, params)?;
+        let ciphertext = Self {
+            syndrome: reader.read_bits(params.p)?,
+            masked_seed: reader.read_array()?,
+        };
+        reader.finish()?;
+        Ok(ciphertext)
+    }
+}
+
+/// H: error vector of length `n` and weight `t` derived from the seed
+pub(super) fn seed_to_error_vector(seed: &[u8; KEM_SECRET_SIZE], n: usize, t: usize) -> BitVector {
+    let mut input = Vec::with_capacity(KEM_SECRET_SIZE + 1);
+    input.push(ERROR_VECTOR_DOMAIN);
+    input.extend_from_slice(seed);
+    generate_seeded_weight_vector(&input, n, t)
+}
+
+/// L: mask applied to the seed
+pub(super) fn error_vector_to_seed_mask(error_vector: &BitVector) -> [u8; KEM_SECRET_SIZE] {
+    let mut hasher = Sha3_256::new();
+    hasher.update([SEED_MASK_DOMAIN]);
+    hasher.update(error_vector.to_bytes());
+    hasher.finalize().into()
+}
+
+/// K: shared secret, bound to the whole ciphertext
+pub(super) fn shared_secret(
+    seed: &[u8; KEM_SECRET_SIZE],
+    ciphertext: &KemCiphertext,
+) -> [u8; KEM_SECRET_SIZE] {
+    let mut hasher = Sha3_256::new();
+    hasher.update([SHARED_SECRET_DOMAIN]);
+    hasher.update(seed);
+    hasher.update(ciphertext.syndrome.to_bytes());
+    hasher.update(ciphertext.masked_seed);
+    hasher.finalize().into()
+}
+
+pub(super) fn xor_secrets(
+    a: &[u8; KEM_SECRET_SIZE],
+    b: &[u8; KEM_SECRET_SIZE],
+) -> [u8; KEM_SECRET_SIZE] {
+    let mut result = [0u8; KEM_SECRET_SIZE];
+    for (r, (x, y)) in result.iter_mut().zip(a.iter().zip(b.iter())) {
+        *r = x ^ y;
+    }
+    result
+}
@@ -0,0 +1,261 @@
+use crate::params::PARAMS_IDENTIFIER_SIZE;
+use crate::{Error, Params};
+use rand_core::{impls, CryptoRng, RngCore};
+use sha3::digest::core_api::{Block, ExtendableOutputCore, XofReaderCore};
+use sha3::digest::Update;
+use sha3::{Shake256, Shake256ReaderCore};
+use std::fmt;
+use std::str::FromStr;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Size in bytes of the seed all the secrets of a node are derived from
+pub const NODE_SEED_SIZE: usize = 32;
+
+const SEED_EXPANSION_DOMAIN: &[u8] = b"democratic_pq_cle node key v2";
+const BACKUP_PREFIX: &str = "dpqcle-node-key-v2";
+
+/// Random generator reading the output of
+/// `SHAKE256("democratic_pq_cle node key v2" || node_id || params identifier || seed)`, the node
+/// identifier being encoded as u64 LE. The code and KKS dimensions are bound through
+/// [`Params::identifier`], so the same seed never yields related keys under two parameter sets,
+/// while the voting policy can change.
+///
+/// `CertificatelessQcMdpc::from_seed` draws all the node's secrets from it, in the same order and
+/// with the same sampling procedures as `CertificatelessQcMdpc::init_with_rng`. These procedures
+/// only read bytes with `fill_bytes` and apply fixed rejection rules (see
+/// `generate_random_weight_vector` and `generate_random_biguint_below`), so that a backup restores
+/// the same keys whatever the versions of `rand` and `num-bigint`.
+///
+/// The SHAKE256 output is read block by block, so that the buffered output is wiped on drop along
+/// with the Keccak state.
+pub(super) struct SeedExpander {
+    reader: Shake256ReaderCore,
+    block: Block<Shake256ReaderCore>,
+    /// Position of the first unread byte of `block`
+    position: usize,
+}
+
+impl SeedExpander {
+    pub(super) fn new(seed: &[u8; NODE_SEED_SIZE], node_id: usize, params: &Params) -> Self {
+        let mut hasher = Shake256::default();
+        hasher.update(SEED_EXPANSION_DOMAIN);
+        hasher.update(&(node_id as u64).to_le_bytes());
+        hasher.update(&params.identifier());
+        hasher.update(seed);
+        let (mut core, mut buffer) = hasher.decompose();
+        let reader = core.finalize_xof_core(&mut buffer);
+        // The buffer still holds the end of the seed
+        buffer.pad_with_zeros().as_mut_slice().zeroize();
+        let block = Block::<Shake256ReaderCore>::default();
+        Self {
+            reader,
+            position: block.len(),
+            block,
+        }
+    }
+}
+
+impl Drop for SeedExpander {
+    fn drop(&mut self) {
+        self.block.as_mut_slice().zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SeedExpander {}
+
+impl RngCore for SeedExpander {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            if self.position == self.block.len() {
+                self.block = self.reader.read_block();
+                self.position = 0;
+            }
+            *byte = self.block[self.position];
+            self.position += 1;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for SeedExpander {}
+
+/// Everything needed, together with the public `s_i`, to restore a node's secrets.
+///
+/// Printed as `dpqcle-node-key-v2:<node_id>:<params identifier>:<seed>`, the last two in
+/// hexadecimal. The seed is wiped on drop and `Debug` doesn't print it.
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeKeyBackup {
+    pub node_id: usize,
+    /// [`Params::identifier`] of the parameter set the node was generated with
+    pub params_identifier: [u8; PARAMS_IDENTIFIER_SIZE],
+    pub seed: [u8; NODE_SEED_SIZE],
+}
+
+impl fmt::Debug for NodeKeyBackup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeKeyBackup")
+            .field("node_id", &self.node_id)
+            .field("params_identifier", &self.params_identifier)
+            .field("seed", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Drop for NodeKeyBackup {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for NodeKeyBackup {}
+
+impl fmt::Display for NodeKeyBackup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:", BACKUP_PREFIX, self.node_id)?;
+        for byte in self.params_identifier {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ":")?;
+        for byte in self.seed {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for NodeKeyBackup {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.trim().split(':').collect();
+        if fields.len() != 4 || fields[0] != BACKUP_PREFIX {
+            return Err(Error::InvalidEncoding("invalid node key backup"));
+        }
+        // `parse` and `from_str_radix` accept a leading sign, which would give several encodings
+        // of the same backup
+        let node_id = fields[1];
+        if node_id.is_empty() || !node_id.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(Error::InvalidEncoding("invalid node key backup"));
+        }
+        let node_id = node_id
+            .parse::<usize>()
+            .map_err(|_| Error::InvalidEncoding("invalid node key backup"))?;
+        let mut params_identifier = [0u8; PARAMS_IDENTIFIER_SIZE];
+        parse_hex(fields[2], &mut params_identifier)
+            .map_err(|_| Error::InvalidEncoding("invalid node key backup parameters identifier"))?;
+        let mut seed = [0u8; NODE_SEED_SIZE];
+        parse_hex(fields[3], &mut seed)
+            .map_err(|_| Error::InvalidEncoding("invalid node key backup seed"))?;
+        let backup = Self {
+            node_id,
+            params_identifier,
+            seed,
+        };
+        seed.zeroize();
+        Ok(backup)
+    }
+}
+
+/// Decodes exactly `bytes.len()` bytes of lowercase or uppercase hexadecimal
+fn parse_hex(hex: &str, bytes: &mut [u8]) -> Result<(), ()> {
+    if hex.len() != bytes.len() << 1 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(());
+    }
+    for (byte_pos, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[byte_pos << 1..(byte_pos + 1) << 1], 16).map_err(|_| ())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NodeKeyBackup, NODE_SEED_SIZE};
+    use crate::certificateless_qc_mdpc::utils::generate_seeded_weight_vector;
+    use crate::certificateless_qc_mdpc::{test_node, CertificatelessQcMdpc};
+    use crate::{Error, Params};
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+    use sha3::{Digest, Sha3_256};
+
+    #[test]
+    fn test_backup_round_trip() {
+        let params = Params::TEST;
+        let (node, s_i) = test_node(3, &params, &mut ChaCha20Rng::seed_from_u64(14));
+        let backup = node.backup();
+        let text = backup.to_string();
+        assert_eq!(text.parse::<NodeKeyBackup>(), Ok(node.backup()));
+        let restored = CertificatelessQcMdpc::restore(&backup, &params, &s_i).unwrap();
+        assert!(restored.private_key() == node.private_key());
+        assert_eq!(
+            restored.public_key_and_witness().unwrap(),
+            node.public_key_and_witness().unwrap()
+        );
+
+        let seed_start = text.rfind(':').unwrap() + 1;
+        let node_id_start = text.find(':').unwrap() + 1;
+        for invalid in [
+            format!("{}+f{}", &text[..seed_start], &text[seed_start + 2..]),
+            format!("{}-0{}", &text[..seed_start], &text[seed_start + 2..]),
+            format!("{}g{}", &text[..seed_start], &text[seed_start + 1..]),
+            format!("{}+{}", &text[..node_id_start], &text[node_id_start..]),
+            text[..text.len() - 2].to_string(),
+        ] {
+            assert!(invalid.parse::<NodeKeyBackup>().is_err(), "{}", invalid);
+        }
+
+        // The KKS dimensions are bound as well, not only p, w and t, but not the voting policy
+        let other_kks = Params { sig_r: 38, ..params };
+        assert_eq!(
+            CertificatelessQcMdpc::restore(&backup, &other_kks, &s_i).err(),
+            Some(Error::ParametersMismatch)
+        );
+        let other_policy = Params {
+            votes_threshold: 0.5,
+            ..params
+        };
+        let restored = CertificatelessQcMdpc::restore(&backup, &other_policy, &s_i).unwrap();
+        assert!(restored.private_key() == node.private_key());
+    }
+
+    /// Pins the keys derived from a fixed seed, so that no change of the derivation or of a
+    /// dependency silently restores other keys from existing backups
+    #[test]
+    fn test_known_answer() {
+        let params = Params::TEST;
+        let s_i = generate_seeded_weight_vector(b"s_i", params.p, params.s_i_weight());
+        let seed: [u8; NODE_SEED_SIZE] = core::array::from_fn(|i| i as u8);
+        let node = CertificatelessQcMdpc::from_seed(5, &params, &s_i, &seed).unwrap();
+        let (public_key, witness) = node.public_key_and_witness().unwrap();
+        let digest = |bytes: Vec<u8>| {
+            Sha3_256::digest(bytes)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        };
+        assert_eq!(
+            digest(node.private_key().to_bytes(&params)),
+            "1169684d3e3d58e78bf2d7d7c8707036818f7956cc39594dc1371aed1da27fa1"
+        );
+        assert_eq!(
+            digest(public_key.to_bytes(&params)),
+            "5dea36b4c84573ee23c6d86061dd0f94f912cb96ae06d34db5f50a211795cd8e"
+        );
+        assert_eq!(
+            digest(witness.to_bytes(&params)),
+            "421d65a2188cbd424eab76259757ea2e485eb0cf5ff840f071744dbae34a5562"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use crate::bit_vector::BitVector;
+use crate::Error;
+use crate::certificateless_qc_mdpc::utils::{
+    frame_message, generate_seeded_weight_vector, unframe_message, MESSAGE_LENGTH_PREFIX_SIZE,
+};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+
+/// Size in bytes of the random seed `r`
+pub(super) const KOBARA_IMAI_SEED_SIZE: usize = 32;
+const SEED_SIZE_BITS: usize = KOBARA_IMAI_SEED_SIZE << 3;
+/// Smallest message word size, in bits, leaving room for the seed and the message length prefix
+pub(crate) const KOBARA_IMAI_MIN_MESSAGE_WORD_SIZE: usize =
+    SEED_SIZE_BITS + (MESSAGE_LENGTH_PREFIX_SIZE << 3);
+
+const MESSAGE_MASK_DOMAIN: u8 = b'G';
+const SEED_MASK_DOMAIN: u8 = b'H';
+const ERROR_VECTOR_DOMAIN: u8 = b'E';
+
+/// Size in bits available for the framed message in a message word of `message_word_size` bits
+pub(super) fn kobara_imai_framed_message_size(message_word_size: usize) -> usize {
+    message_word_size - SEED_SIZE_BITS
+}
+
+/// Kobara-Imai γ conversion: returns the message word (of `message_word_size` bits) and the error
+/// vector (of length `n` and weight `t`) to encrypt `data` with.
+///
+/// The message word `y2 || y1` is made of the framed message masked with a stream derived from the
+/// random seed `r` (`y1`), and of the seed masked with a hash of `y1` (`y2`). Instead of encoding
+/// part of the data in the error vector, the error vector is derived from `r` and `y1`, which lets
+/// the decryption check that the ciphertext was honestly generated.
+pub(super) fn kobara_imai_encode(
+    data: &[u8],
+    seed: &[u8; KOBARA_IMAI_SEED_SIZE],
+    message_word_size: usize,
+    n: usize,
+    t: usize,
+) -> (BitVector, BitVector) {
+    let framed_size = kobara_imai_framed_message_size(message_word_size);
+    let y1 = &frame_message(data, framed_size) ^ &shake(MESSAGE_MASK_DOMAIN, &[seed], framed_size);
+    let y2 = &BitVector::from_bytes(seed, SEED_SIZE_BITS)
+        ^ &shake(SEED_MASK_DOMAIN, &[&y1.to_bytes()], SEED_SIZE_BITS);
+    let error_vector = error_vector(seed, &y1, n, t);
+    (y2.concat(&y1), error_vector)
+}
+
+/// Inverse of [`kobara_imai_encode`], fails if `error_vector` isn't the one derived from the
+/// message word
+pub(super) fn kobara_imai_decode(
+    message_word: &BitVector,
+    error_vector: &BitVector,
+    t: usize,
+) -> Result<Vec<u8>, Error> {
+    let y2 = message_word.slice(0..SEED_SIZE_BITS);
+    let y1 = message_word.slice(SEED_SIZE_BITS..message_word.len());
+    let seed_bits = &y2 ^ &shake(SEED_MASK_DOMAIN, &[&y1.to_bytes()], SEED_SIZE_BITS);
+    let seed: [u8; KOBARA_IMAI_SEED_SIZE] = seed_bits.to_bytes().try_into().unwrap();
+    if error_vector.weight() != t
+        || *error_vector != self::error_vector(&seed, &y1, error_vector.len(), t)
+    {
+        return Err(Error::InvalidCiphertext);
+    }
+    unframe_message(&(&y1 ^ &shake(MESSAGE_MASK_DOMAIN, &[&seed], y1.len())))
+}
+
+fn error_vector(
+    seed: &[u8; KOBARA_IMAI_SEED_SIZE],
+    y1: &BitVector,
+    n: usize,
+    t: usize,
+) -> BitVector {
+    let mut input = vec![ERROR_VECTOR_DOMAIN];
+    input.extend_from_slice(seed);
+    input.extend_from_slice(&y1.to_bytes());
+    generate_seeded_weight_vector(&input, n, t)
+}
+
+/// SHAKE256(domain || inputs), on `size` bits
+fn shake(domain: u8, inputs: &[&[u8]], size: usize) -> BitVector {
+    let mut hasher = Shake256::default();
+    hasher.update(&[domain]);
+    for input in inputs {
+        hasher.update(input);
+    }
+    let mut output = vec![0u8; size.div_ceil(8)];
+    hasher.finalize_xof().read(&mut output);
+    BitVector::from_bytes(&output, size)
+}
@@ -0,0 +1,373 @@
+use crate::bit_vector::BitVector;
+use crate::certificateless_qc_mdpc::{
+    CertificatelessQcMdpcPublicKey, NewNodeAcceptanceSignature, NodeWitnessSigPubKey,
+    SHARE_BINDING_SIZE,
+};
+use crate::math::{binom, nth_combination};
+use crate::{Error, Params};
+use num::Integer;
+use num_bigint::{BigInt, BigUint, ToBigInt};
+use num_primes::Verification;
+use std::collections::BTreeMap;
+use verifiable_secret_sharing::ShamirSecretSharing as SSS;
+
+/// Public data of a node of the network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub s_i: BitVector,
+    pub public_key: CertificatelessQcMdpcPublicKey,
+    pub witness: NodeWitnessSigPubKey,
+}
+
+/// View of the network shared by every node: the members, the votes for the candidates, and the
+/// `s_i` of the admitted nodes that haven't published their public key yet.
+///
+/// The admission of a node goes through [`Self::add_share_binding`] for each member's
+/// published binding to its share, [`Self::add_vote`] for each acceptance signature the
+/// candidate received, [`Self::admit`] once enough members voted, then [`Self::register`] with the
+/// public key the new node generated from its `s_i`. Only votes checked by [`Self::verify_vote`]
+/// reach the Shamir recovery, so a faulty voter is reported instead of silently corrupting `s_i`.
+#[derive(Debug, Clone)]
+pub struct MembershipRegistry {
+    params: Params,
+    shamir_prime: BigInt,
+    members: BTreeMap<usize, Member>,
+    share_bindings: BTreeMap<usize, BTreeMap<usize, [u8; SHARE_BINDING_SIZE]>>,
+    votes: BTreeMap<usize, BTreeMap<usize, NewNodeAcceptanceSignature>>,
+    admitted: BTreeMap<usize, BitVector>,
+}
+
+impl MembershipRegistry {
+    /// Empty registry, using [`Params::shamir_prime`]
+    pub fn new(params: &Params) -> Result<Self, Error> {
+        params.validate()?;
+        Ok(Self::from_prime(params, params.shamir_prime()))
+    }
+
+    /// Same as [`Self::new`] with another prime larger than `2^sig_n`, which all the nodes must
+    /// use
+    pub fn with_shamir_prime(params: &Params, shamir_prime: BigUint) -> Result<Self, Error> {
+        params.validate()?;
+        if shamir_prime.bits() <= params.sig_n as u64 {
+            return Err(Error::InvalidParameters("the Shamir prime must be larger than 2^sig_n, otherwise shares could exceed it"));
+        }
+        if !Verification::is_prime(&shamir_prime) {
+            return Err(Error::InvalidParameters("the Shamir prime isn't prime"));
+        }
+        Ok(Self::from_prime(params, shamir_prime))
+    }
+
+    fn from_prime(params: &Params, shamir_prime: BigUint) -> Self {
+        Self {
+            params: *params,
+            shamir_prime: shamir_prime.to_bigint().unwrap(),
+            members: BTreeMap::new(),
+            share_bindings: BTreeMap::new(),
+            votes: BTreeMap::new(),
+            admitted: BTreeMap::new(),
+        }
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn members_count(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_member(&self, node_id: usize) -> bool {
+        self.members.contains_key(&node_id)
+    }
+
+    pub fn member(&self, node_id: usize) -> Option<&Member> {
+        self.members.get(&node_id)
+    }
+
+    /// Number of votes a candidate needs with the current members
+    pub fn votes_needed(&self) -> usize {
+        self.params.votes_needed(self.members.len()).max(1)
+    }
+
+    /// Number of valid votes collected for `candidate_id`
+    pub fn votes_count(&self, candidate_id: usize) -> usize {
+        self.votes.get(&candidate_id).map_or(0, BTreeMap::len)
+    }
+
+    /// Adds the first member of the network, with a random `s_i` and without a vote. Once the
+    /// registry has a member, every other node has to be voted in.
+    pub fn add_founding_member(
+        &mut self,
+        node_id: usize,
+        s_i: BitVector,
+        public_key: CertificatelessQcMdpcPublicKey,
+        witness: NodeWitnessSigPubKey,
+    ) -> Result<(), Error> {
+        if !self.members.is_empty() {
+            return Err(Error::AlreadyBootstrapped);
+        }
+        public_key.verify(node_id, &s_i, &witness, &self.params)?;
+        self.members.insert(
+            node_id,
+            Member {
+                s_i,
+                public_key,
+                witness,
+            },
+        );
+        Ok(())
+    }
+
+    /// Records the binding a member published to its share for `candidate_id`, see
+    /// [`NewNodeAcceptanceSignature::share_binding`]. It must be recorded before the vote itself.
+    pub fn add_share_binding(
+        &mut self,
+        candidate_id: usize,
+        signer_id: usize,
+        binding: [u8; SHARE_BINDING_SIZE],
+    ) -> Result<(), Error> {
+        if self.is_member(candidate_id) || self.admitted.contains_key(&candidate_id) {
+            return Err(Error::AlreadyMember(candidate_id));
+        }
+        if !self.is_member(signer_id) {
+            return Err(Error::UnknownMember(signer_id));
+        }
+        let candidate_bindings = self.share_bindings.entry(candidate_id).or_default();
+        if candidate_bindings.contains_key(&signer_id) {
+            return Err(Error::DuplicateVote(signer_id));
+        }
+        candidate_bindings.insert(signer_id, binding);
+        Ok(())
+    }
+
+    /// Checks a vote share for `candidate_id` without recording it: the share must match the
+    /// binding its signer published with [`Self::add_share_binding`], and the signature
+    /// must verify against the signer's witness. A candidate can thus tell which of the shares
+    /// it received are bad, and from whom, before combining them.
+    pub fn verify_vote(
+        &self,
+        candidate_id: usize,
+        signature: &NewNodeAcceptanceSignature,
+    ) -> Result<(), Error> {
+        if self.is_member(candidate_id) || self.admitted.contains_key(&candidate_id) {
+            return Err(Error::AlreadyMember(candidate_id));
+        }
+        let signer_id = signature.signing_node_id;
+        let signer = self
+            .members
+            .get(&signer_id)
+            .ok_or(Error::UnknownMember(signer_id))?;
+        let binding = self
+            .share_bindings
+            .get(&candidate_id)
+            .and_then(|candidate_bindings| candidate_bindings.get(&signer_id))
+            .ok_or(Error::MissingShareBinding(signer_id))?;
+        if signature.share_binding(candidate_id) != *binding {
+            return Err(Error::ShareBindingMismatch(signer_id));
+        }
+        signature.verify(&signer.witness, candidate_id, &self.params)
+    }
+
+    /// Records the vote of a member for `candidate_id`, once [`Self::verify_vote`] accepts it
+    pub fn add_vote(
+        &mut self,
+        candidate_id: usize,
+        signature: NewNodeAcceptanceSignature,
+    ) -> Result<(), Error> {
+        let signer_id = signature.signing_node_id;
+        if self
+            .votes
+            .get(&candidate_id)
+            .is_some_and(|candidate_votes| candidate_votes.contains_key(&signer_id))
+        {
+            return Err(Error::DuplicateVote(signer_id));
+        }
+        self.verify_vote(candidate_id, &signature)?;
+        self.votes
+            .entry(candidate_id)
+            .or_default()
+            .insert(signer_id, signature);
+        Ok(())
+    }
+
+    /// Recovers the `s_i` of `candidate_id` from [`Self::votes_needed`] of its votes. The
+    /// candidate then initializes itself with it and calls [`Self::register`].
+    pub fn admit(&mut self, candidate_id: usize) -> Result<BitVector, Error> {
+        if self.is_member(candidate_id) {
+            return Err(Error::AlreadyMember(candidate_id));
+        }
+        if let Some(s_i) = self.admitted.get(&candidate_id) {
+            return Ok(s_i.clone());
+        }
+        let votes_needed = self.votes_needed();
+        let votes_count = self.votes_count(candidate_id);
+        if votes_count < votes_needed {
+            return Err(Error::NotEnoughVotes {
+                needed: votes_needed,
+                actual: votes_count,
+            });
+        }
+        let shares: Vec<(usize, BigInt)> = self.votes[&candidate_id]
+            .values()
+            .take(votes_needed)
+            .map(NewNodeAcceptanceSignature::to_shamir_share)
+            .collect();
+        let sss = SSS {
+            threshold: votes_needed,
+            share_amount: self.members.len(),
+            prime: self.shamir_prime.clone(),
+        };
+        let s_i_weight = self.params.s_i_weight();
+        let combinations_count = binom(self.params.p, s_i_weight).to_bigint().unwrap();
+        let combination_index = sss.recover(&shares).mod_floor(&combinations_count);
+        let mut s_i = BitVector::zeros(self.params.p);
+        for index_to_flip in
+            nth_combination(self.params.p, s_i_weight, combination_index.to_biguint().unwrap())
+        {
+            s_i.set(index_to_flip, true);
+        }
+        self.share_bindings.remove(&candidate_id);
+        self.votes.remove(&candidate_id);
+        self.admitted.insert(candidate_id, s_i.clone());
+        Ok(s_i)
+    }
+
+    /// Makes an admitted node a member, once its public key verifies against its `s_i`
+    pub fn register(
+        &mut self,
+        node_id: usize,
+        public_key: CertificatelessQcMdpcPublicKey,
+        witness: NodeWitnessSigPubKey,
+    ) -> Result<(), Error> {
+        let s_i = self
+            .admitted
+            .get(&node_id)
+            .ok_or(Error::NotAdmitted(node_id))?;
+        public_key.verify(node_id, s_i, &witness, &self.params)?;
+        let s_i = self.admitted.remove(&node_id).unwrap();
+        self.members.insert(
+            node_id,
+            Member {
+                s_i,
+                public_key,
+                witness,
+            },
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MembershipRegistry;
+    use crate::bit_vector::BitVector;
+    use crate::certificateless_qc_mdpc::{
+        test_node, test_registry, test_vote, CertificatelessQcMdpc, NewNodeAcceptanceSignature,
+    };
+    use crate::my_bool::MyBool;
+    use crate::{Error, Params};
+    use num_bigint::BigUint;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn test_admission() {
+        let params = Params::TEST;
+        let mut rng = ChaCha20Rng::seed_from_u64(22);
+        let (mut registry, node_1) = test_registry(&params, &mut rng);
+        let (node_5, s_i_5) = test_node(5, &params, &mut rng);
+        let (public_key_5, witness_5) = node_5.public_key_and_witness().unwrap();
+        assert_eq!(
+            registry.add_founding_member(5, s_i_5, public_key_5, witness_5),
+            Err(Error::AlreadyBootstrapped)
+        );
+
+        // The share must match the binding published beforehand
+        assert_eq!(
+            registry.add_vote(2, node_1.accept_new_node(2)),
+            Err(Error::MissingShareBinding(1))
+        );
+        let other_share = node_1.accept_new_node(2);
+        registry
+            .add_share_binding(6, 1, other_share.share_binding(6))
+            .unwrap();
+        assert_eq!(
+            registry.add_vote(6, node_1.accept_new_node(6)),
+            Err(Error::ShareBindingMismatch(1))
+        );
+
+        assert_eq!(test_vote(&mut registry, &node_1, 2), Ok(()));
+        assert_eq!(
+            registry.add_vote(2, node_1.accept_new_node(2)),
+            Err(Error::DuplicateVote(1))
+        );
+        let s_i_2 = registry.admit(2).unwrap();
+        let node_2 = CertificatelessQcMdpc::init_with_rng(2, &params, &s_i_2, &mut rng).unwrap();
+        let (public_key_2, witness_2) = node_2.public_key_and_witness().unwrap();
+        assert_eq!(
+            registry.register(3, public_key_2.clone(), witness_2.clone()),
+            Err(Error::NotAdmitted(3))
+        );
+        // The witness matrices must be the ones derived from r_i and the parameters
+        let mut tampered_witness = witness_2.clone();
+        let entry = &mut tampered_witness.signature_parity_matrix[(0, params.sig_r)];
+        *entry = MyBool::from(!**entry);
+        let mut wrong_shape_witness = witness_2.clone();
+        wrong_shape_witness.signature_multiplication_matrix =
+            witness_2.signature_parity_matrix.clone();
+        for witness in [tampered_witness, wrong_shape_witness] {
+            assert_eq!(
+                registry.register(2, public_key_2.clone(), witness),
+                Err(Error::InvalidWitness)
+            );
+        }
+        registry.register(2, public_key_2, witness_2).unwrap();
+        assert!(registry.is_member(2));
+        assert_eq!(registry.members_count(), 2);
+        assert_eq!(registry.admit(2), Err(Error::AlreadyMember(2)));
+        assert_eq!(test_vote(&mut registry, &node_1, 2), Err(Error::AlreadyMember(2)));
+
+        // Both members now have to vote
+        assert_eq!(registry.votes_needed(), 2);
+        test_vote(&mut registry, &node_2, 3).unwrap();
+        assert_eq!(
+            registry.admit(3),
+            Err(Error::NotEnoughVotes {
+                needed: 2,
+                actual: 1
+            })
+        );
+        test_vote(&mut registry, &node_1, 3).unwrap();
+        assert!(registry.admit(3).is_ok());
+    }
+
+    #[test]
+    fn test_registry_rejections() {
+        // Small signatures, so that the Shamir prime is quickly found
+        let params = Params {
+            sig_k: 27,
+            sig_n: 64,
+            sig_n_prime: 32,
+            sig_r: 40,
+            signature_weight_interval: [10, 20],
+            ..Params::default()
+        };
+        assert!(MembershipRegistry::with_shamir_prime(&params, BigUint::from(7u8)).is_err());
+        let composite = params.shamir_prime() * 3u8;
+        assert!(MembershipRegistry::with_shamir_prime(&params, composite).is_err());
+        let mut registry = MembershipRegistry::new(&params).unwrap();
+        let signature = NewNodeAcceptanceSignature {
+            signing_node_id: 1,
+            signature: BitVector::zeros(params.sig_n),
+        };
+        assert_eq!(registry.verify_vote(2, &signature), Err(Error::UnknownMember(1)));
+        assert_eq!(registry.add_vote(2, signature), Err(Error::UnknownMember(1)));
+        assert_eq!(
+            registry.admit(2),
+            Err(Error::NotEnoughVotes {
+                needed: 1,
+                actual: 0
+            })
+        );
+    }
+}
@@ -1,162 +1,266 @@
+mod armor;
+mod decoder;
 mod encryption_private_key;
 mod encryption_public_key;
+mod hybrid;
+mod kem;
+mod key_derivation;
+mod kobara_imai;
+mod membership_registry;
 mod new_node_acceptance_signature;
 pub mod utils;
+mod wire_format;
 mod witness_signing_pub_key;
 
 use crate::binary_matrix_operations::{
-    concat_horizontally_mat, make_circulant_matrix, make_identity_matrix,
+    concat_horizontally_mat, make_circulant_matrix, make_identity_matrix, vector_matrix_product,
+    zeroize_matrix, SparseCirculantMatrix,
+};
+use crate::bit_vector::BitVector;
+pub use crate::certificateless_qc_mdpc::decoder::{
+    BitFlippingDecoder, BlackGrayFlipDecoder, ConstantTimeBitFlippingDecoder, Decoder,
+    DecoderConfig, DecodingAlgorithm, ThresholdPolicy, TieHandling,
+};
+pub use crate::certificateless_qc_mdpc::encryption_private_key::CertificatelessQcMdpcPrivateKey;
+pub use crate::certificateless_qc_mdpc::hybrid::HybridCiphertext;
+pub use crate::certificateless_qc_mdpc::kem::{KemCiphertext, KEM_SECRET_SIZE};
+pub(crate) use crate::certificateless_qc_mdpc::kobara_imai::KOBARA_IMAI_MIN_MESSAGE_WORD_SIZE;
+use crate::certificateless_qc_mdpc::key_derivation::SeedExpander;
+pub use crate::certificateless_qc_mdpc::key_derivation::{NodeKeyBackup, NODE_SEED_SIZE};
+pub use crate::certificateless_qc_mdpc::membership_registry::{Member, MembershipRegistry};
+pub use crate::certificateless_qc_mdpc::encryption_public_key::CertificatelessQcMdpcPublicKey;
+pub use crate::certificateless_qc_mdpc::new_node_acceptance_signature::{
+    NewNodeAcceptanceSignature, SHARE_BINDING_SIZE,
 };
-use crate::certificateless_qc_mdpc::encryption_private_key::CertificatelessQcMdpcPrivateKey;
-use crate::certificateless_qc_mdpc::encryption_public_key::CertificatelessQcMdpcPublicKey;
-use crate::certificateless_qc_mdpc::new_node_acceptance_signature::NewNodeAcceptanceSignature;
 use crate::certificateless_qc_mdpc::utils::{
-    check_vector_leads_to_invertible_circulant_matrix, generate_hash_id_vector_correct_weight,
-    generate_random_weight_vector, generate_random_weight_vector_to_invertible_matrix,
+    derive_invertible_s_i, generate_hash_id_vector_correct_weight,
+    generate_invertible_hash_id_vector, generate_random_biguint_below,
+    generate_random_weight_vector, generate_random_weight_vector_to_invertible_matrix_with_rng,
 };
-use crate::certificateless_qc_mdpc::witness_signing_pub_key::NodeWitnessSigPubKey;
+use crate::certificateless_qc_mdpc::witness_signing_pub_key::make_signature_parity_matrix;
+pub use crate::certificateless_qc_mdpc::witness_signing_pub_key::NodeWitnessSigPubKey;
 use crate::math::{binom, nth_combination};
 use crate::my_bool::MyBool;
-use crate::{N_0, SIG_K, SIG_N, SIG_N_PRIME, SIG_R};
+use crate::{Error, Params};
 use nalgebra::DMatrix;
 use num::integer::Roots;
-use num_bigint::RandBigInt;
-use crate::utils::{try_invert_matrix_vector, multiply_2_matrix_first_line_vector};
+use rand_chacha::ChaCha20Rng;
+use rand_core::{CryptoRng, RngCore, SeedableRng};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+use crate::utils::{try_invert_matrix_vector, multiply_2_matrix_first_line_vector, transpose_matrix_first_line_vector};
 
-#[derive(Debug, Clone)]
+/// A node and its secrets, which are wiped on drop. `Debug` doesn't print them, and copies have to
+/// be made explicitly with [`Self::clone_secret`].
 pub struct CertificatelessQcMdpc {
-    p: usize,
-    t: usize,
-    n: usize,
-    w: usize,
-    secret_vector: Vec<MyBool>,
-    h_i_1: Vec<MyBool>,
-    h_i_2: Vec<MyBool>,
-    h_i_3: Vec<MyBool>,
+    params: Params,
+    secret_vector: BitVector,
+    h_i_1: BitVector,
+    h_i_2: BitVector,
+    h_i_3: BitVector,
     node_id: usize,
     sig_sk_generator: DMatrix<MyBool>,
     sig_j: Vec<usize>,
+    implicit_rejection_secret: [u8; KEM_SECRET_SIZE],
+    seed: [u8; NODE_SEED_SIZE],
 }
 
 impl CertificatelessQcMdpc {
-    #[allow(non_snake_case)]
-    pub fn init(id: usize, p: usize, w: usize, t: usize, si: &[MyBool]) -> Self {
-        assert!(check_vector_leads_to_invertible_circulant_matrix(si, p));
-        assert_eq!(si.len(), p);
+    pub fn init(id: usize, params: &Params, si: &BitVector) -> Result<Self, Error> {
+        Self::init_with_rng(id, params, si, &mut ChaCha20Rng::from_entropy())
+    }
+
+    /// Same as [`Self::init`], drawing the node's seed from `rng`
+    pub fn init_with_rng<R: RngCore + CryptoRng>(
+        id: usize,
+        params: &Params,
+        si: &BitVector,
+        rng: &mut R,
+    ) -> Result<Self, Error> {
+        let mut seed = Zeroizing::new([0u8; NODE_SEED_SIZE]);
+        rng.fill_bytes(seed.as_mut());
+        Self::from_seed(id, params, si, &seed)
+    }
 
-        let h_i_1_weight = (w >> 1).nth_root(3);
-        let h_i_2_weight = (w >> 1).nth_root(3);
-        let h_i_3_weight = w >> 1;
-        let h_i_1 = generate_hash_id_vector_correct_weight(id, p, h_i_1_weight);
-        assert!(check_vector_leads_to_invertible_circulant_matrix(&h_i_1, p)); // What to do otherwise?
+    /// Derives all the node's secrets from `seed`, see [`NodeKeyBackup`].
+    ///
+    /// Fails if `params` is invalid or if `si` doesn't have length `p`. A non-invertible `si` or
+    /// identifier hash is replaced by a publicly reproducible one, which
+    /// [`CertificatelessQcMdpcPublicKey::check_is_valid`] derives the same way.
+    #[allow(non_snake_case)]
+    pub fn from_seed(
+        id: usize,
+        params: &Params,
+        si: &BitVector,
+        seed: &[u8; NODE_SEED_SIZE],
+    ) -> Result<Self, Error> {
+        params.validate()?;
+        let p = params.p;
+        let rng = &mut SeedExpander::new(seed, id, params);
+        if si.len() != p {
+            return Err(Error::InvalidLength {
+                expected: p,
+                actual: si.len(),
+            });
+        }
+        let si = derive_invertible_s_i(si, params.s_i_weight())?;
 
-        //println!("h_i_1: {:?}", h_i_1);
+        let h_i_1_weight = params.h_i_1_weight();
+        let h_i_2_weight = params.h_i_2_weight();
+        let h_i_3_weight = params.h_i_3_weight();
+        let h_i_1 = generate_invertible_hash_id_vector(id, p, h_i_1_weight)?;
 
-        let h_i_2 = generate_random_weight_vector_to_invertible_matrix(p, h_i_2_weight);
-        //println!("h_i_2: {:?}", h_i_2);
+        let h_i_2 =
+            generate_random_weight_vector_to_invertible_matrix_with_rng(p, h_i_2_weight, rng);
 
-        //println!("h_i_3: generated");
-        let h_i_3 = generate_random_weight_vector(p, h_i_3_weight);
-        //println!("h_i_3: {:?}", h_i_3);
+        let h_i_3 = generate_random_weight_vector(p, h_i_3_weight, rng);
 
-        let sig_a = make_circulant_matrix(
-            &generate_random_weight_vector_to_invertible_matrix(SIG_K, SIG_K.nth_root(3)),
-            SIG_K,
-            SIG_K,
+        let (sig_k, sig_n, sig_n_prime) = (params.sig_k, params.sig_n, params.sig_n_prime);
+        let mut sig_a = make_circulant_matrix(
+            &generate_random_weight_vector_to_invertible_matrix_with_rng(
+                sig_k,
+                sig_k.nth_root(3),
+                rng,
+            ),
+            sig_k,
+            sig_k,
             1,
         );
-        let mut sig_g = make_identity_matrix(SIG_K);
-        let b = generate_random_weight_vector(SIG_N_PRIME - SIG_K, (SIG_N_PRIME - SIG_K) >> 1);
-        let B = make_circulant_matrix(&b, SIG_K, SIG_N_PRIME - SIG_K, 1);
+        let mut sig_g = make_identity_matrix(sig_k);
+        let mut b = generate_random_weight_vector(
+            sig_n_prime - sig_k,
+            (sig_n_prime - sig_k) >> 1,
+            rng,
+        );
+        let mut B = make_circulant_matrix(&b, sig_k, sig_n_prime - sig_k, 1);
         concat_horizontally_mat(&mut sig_g, &B);
 
-        let sig_sk_generator = sig_a * sig_g;
+        let sig_sk_generator = &sig_a * &sig_g;
+        b.zeroize();
+        zeroize_matrix(&mut sig_a);
+        zeroize_matrix(&mut sig_g);
+        zeroize_matrix(&mut B);
 
-        let mut rng = rand::thread_rng();
-        let j_comb_index =
-            rng.gen_biguint_below(&binom(SIG_N, SIG_N_PRIME));
-        let j_comb = nth_combination(SIG_N, SIG_N_PRIME, j_comb_index);
+        let j_comb_index = generate_random_biguint_below(&binom(sig_n, sig_n_prime), rng);
+        let j_comb = nth_combination(sig_n, sig_n_prime, j_comb_index);
 
-        Self {
-            p,
-            t,
-            n: p * N_0,
-            w,
-            secret_vector: si.to_vec(),
+        let mut implicit_rejection_secret = Zeroizing::new([0u8; KEM_SECRET_SIZE]);
+        rng.fill_bytes(implicit_rejection_secret.as_mut());
+
+        Ok(Self {
+            params: *params,
+            secret_vector: si,
             h_i_1,
             h_i_2,
             h_i_3,
             node_id: id,
             sig_sk_generator,
             sig_j: j_comb,
+            implicit_rejection_secret: *implicit_rejection_secret,
+            seed: *seed,
+        })
+    }
+
+    /// Seed and parameters of the node, to be stored somewhere safe
+    pub fn backup(&self) -> NodeKeyBackup {
+        NodeKeyBackup {
+            node_id: self.node_id,
+            params_identifier: self.params.identifier(),
+            seed: self.seed,
+        }
+    }
+
+    /// Inverse of [`Self::backup`], `si` being the node's initialization vector
+    pub fn restore(
+        backup: &NodeKeyBackup,
+        params: &Params,
+        si: &BitVector,
+    ) -> Result<Self, Error> {
+        if backup.params_identifier != params.identifier() {
+            return Err(Error::ParametersMismatch);
         }
+        Self::from_seed(backup.node_id, params, si, &backup.seed)
     }
 
     #[allow(non_snake_case)]
-    pub fn public_key_and_witness(&self) -> (CertificatelessQcMdpcPublicKey, NodeWitnessSigPubKey) {
-        let h_i_1_inv = try_invert_matrix_vector(&self.h_i_1).unwrap();
-        let h_i_2_inv = try_invert_matrix_vector(&self.h_i_2).unwrap();
-        let s_i_inv = try_invert_matrix_vector(&self.secret_vector).unwrap();
+    pub fn public_key_and_witness(
+        &self,
+    ) -> Result<(CertificatelessQcMdpcPublicKey, NodeWitnessSigPubKey), Error> {
+        let h_i_1_inv = try_invert_matrix_vector(&self.h_i_1).ok_or(Error::NonInvertibleVector)?;
+        let h_i_2_inv = Zeroizing::new(
+            try_invert_matrix_vector(&self.h_i_2).ok_or(Error::NonInvertibleVector)?,
+        );
+        let s_i_inv =
+            try_invert_matrix_vector(&self.secret_vector).ok_or(Error::NonInvertibleVector)?;
 
         let r_i = multiply_2_matrix_first_line_vector(&h_i_2_inv, &self.h_i_3);
-        let R_i = make_circulant_matrix(&r_i, self.p, self.p, 1);
-        let right_part_generator = make_circulant_matrix(&multiply_2_matrix_first_line_vector(&multiply_2_matrix_first_line_vector(&multiply_2_matrix_first_line_vector(&s_i_inv, &h_i_1_inv), &h_i_2_inv), &self.h_i_3), self.p, self.p, 1).transpose();
-
-        //let right_part_generator = (S_i_inv * H_i_1_inv * H_i_2_inv * H_i_3).transpose();
-
-        let mut generator = make_identity_matrix(self.p);
-        concat_horizontally_mat(&mut generator, &right_part_generator);
+        // (S_i_inv * H_i_1_inv * H_i_2_inv * H_i_3)^T
+        let inverses_product = Zeroizing::new(multiply_2_matrix_first_line_vector(
+            &multiply_2_matrix_first_line_vector(&s_i_inv, &h_i_1_inv),
+            &h_i_2_inv,
+        ));
+        let generator_first_line = transpose_matrix_first_line_vector(
+            &multiply_2_matrix_first_line_vector(&inverses_product, &self.h_i_3),
+        );
 
-        let mut signature_parity_matrix = make_identity_matrix(SIG_R);
-        let R_i_truncated: DMatrix<MyBool> = R_i.columns(0, SIG_N - SIG_R).into();
-        let R_i_truncated: DMatrix<MyBool> = R_i_truncated.rows(0, SIG_R).into();
-        concat_horizontally_mat(&mut signature_parity_matrix, &R_i_truncated);
+        let (sig_n_prime, sig_r) = (self.params.sig_n_prime, self.params.sig_r);
+        let signature_parity_matrix = make_signature_parity_matrix(&r_i, &self.params);
 
         let mut signature_parity_matrix_truncated =
-            DMatrix::from_element(SIG_R, SIG_N_PRIME, MyBool::from(false));
+            DMatrix::from_element(sig_r, sig_n_prime, MyBool::from(false));
         for col_num in 0..signature_parity_matrix_truncated.ncols() {
             signature_parity_matrix_truncated.set_column(
                 col_num,
                 &signature_parity_matrix.column(self.sig_j[col_num]),
             );
         }
+        let mut sig_sk_generator_transpose = self.sig_sk_generator.transpose();
         let signature_multiplication_matrix =
-            signature_parity_matrix_truncated * self.sig_sk_generator.clone().transpose();
+            &signature_parity_matrix_truncated * &sig_sk_generator_transpose;
+        // The truncated matrix gives away sig_j
+        zeroize_matrix(&mut signature_parity_matrix_truncated);
+        zeroize_matrix(&mut sig_sk_generator_transpose);
 
-        (
+        Ok((
             CertificatelessQcMdpcPublicKey {
-                generator_matrix: generator,
-                max_message_size_bits: self.p,
-                errors_count: self.t,
+                generator_first_line,
+                max_message_size_bits: self.params.p,
+                encoded_vector_size: self.params.n(),
+                errors_count: self.params.t,
             },
             NodeWitnessSigPubKey {
                 pubkey_witness_vector: r_i,
                 signature_parity_matrix: signature_parity_matrix,
                 signature_multiplication_matrix,
             },
-        )
+        ))
     }
 
-    #[allow(non_snake_case)]
     pub fn private_key(&self) -> CertificatelessQcMdpcPrivateKey {
-        let H_i_3 = make_circulant_matrix(&self.h_i_3, self.p, self.p, 1);
-
-        let mut parity_check_matrix = H_i_3;
-        let right_part_parity_check = make_circulant_matrix(&multiply_2_matrix_first_line_vector(&multiply_2_matrix_first_line_vector(&self.h_i_2, &self.h_i_1), &self.secret_vector), self.p, self.p, 1);
-
-        concat_horizontally_mat(&mut parity_check_matrix, &right_part_parity_check);
+        let h_i_2_h_i_1 =
+            Zeroizing::new(multiply_2_matrix_first_line_vector(&self.h_i_2, &self.h_i_1));
+        let right_part_parity_check = Zeroizing::new(multiply_2_matrix_first_line_vector(
+            &h_i_2_h_i_1,
+            &self.secret_vector,
+        ));
 
-        CertificatelessQcMdpcPrivateKey {
-            parity_check_matrix,
-            expected_encoded_vector_size: self.n,
-        }
+        CertificatelessQcMdpcPrivateKey::new(
+            vec![
+                SparseCirculantMatrix::from_first_row(&self.h_i_3),
+                SparseCirculantMatrix::from_first_row(&right_part_parity_check),
+            ],
+            &self.params,
+            DecodingAlgorithm::default(),
+            self.implicit_rejection_secret,
+        )
     }
 
     #[allow(non_snake_case)]
     pub fn accept_new_node(&self, new_node_id: usize) -> NewNodeAcceptanceSignature {
         // Returns Shamir's share
+        let (sig_k, sig_n) = (self.params.sig_k, self.params.sig_n);
         let mut generator_star: DMatrix<MyBool> =
-            DMatrix::from_element(SIG_K, SIG_N, MyBool::from(false));
+            DMatrix::from_element(sig_k, sig_n, MyBool::from(false));
         for col in 0..generator_star.ncols() {
             let current_col_pos_in_sig_j = self.sig_j.iter().position(|&c| c == col);
             for row in 0..generator_star.nrows() {
@@ -168,9 +272,9 @@ impl CertificatelessQcMdpc {
                 }
             }
         }
-        let h_other_1 = generate_hash_id_vector_correct_weight(new_node_id, SIG_K, SIG_K >> 1);
-        let H_other_1: DMatrix<MyBool> = DMatrix::from_column_slice(1, SIG_K, &h_other_1);
-        let signature = H_other_1 * generator_star;
+        let h_other_1 = generate_hash_id_vector_correct_weight(new_node_id, sig_k, sig_k >> 1);
+        let signature = vector_matrix_product(&h_other_1, &generator_star);
+        zeroize_matrix(&mut generator_star);
 
         NewNodeAcceptanceSignature {
             signature,
@@ -178,3 +282,101 @@ impl CertificatelessQcMdpc {
         }
     }
 }
+
+impl CertificatelessQcMdpc {
+    /// Explicit copy of the node and of its secrets
+    pub fn clone_secret(&self) -> Self {
+        Self {
+            params: self.params,
+            secret_vector: self.secret_vector.clone(),
+            h_i_1: self.h_i_1.clone(),
+            h_i_2: self.h_i_2.clone(),
+            h_i_3: self.h_i_3.clone(),
+            node_id: self.node_id,
+            sig_sk_generator: self.sig_sk_generator.clone(),
+            sig_j: self.sig_j.clone(),
+            implicit_rejection_secret: self.implicit_rejection_secret,
+            seed: self.seed,
+        }
+    }
+}
+
+impl fmt::Debug for CertificatelessQcMdpc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertificatelessQcMdpc")
+            .field("params", &self.params)
+            .field("node_id", &self.node_id)
+            .field("secrets", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Drop for CertificatelessQcMdpc {
+    fn drop(&mut self) {
+        self.h_i_2.zeroize();
+        self.h_i_3.zeroize();
+        zeroize_matrix(&mut self.sig_sk_generator);
+        self.sig_j.zeroize();
+        self.implicit_rejection_secret.zeroize();
+        self.seed.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for CertificatelessQcMdpc {}
+
+/// Node initialized with a random `s_i`, everything being drawn from `rng`
+#[cfg(test)]
+pub(crate) fn test_node(
+    node_id: usize,
+    params: &Params,
+    rng: &mut ChaCha20Rng,
+) -> (CertificatelessQcMdpc, BitVector) {
+    let s_i = generate_random_weight_vector_to_invertible_matrix_with_rng(
+        params.p,
+        params.s_i_weight(),
+        rng,
+    );
+    let node = CertificatelessQcMdpc::init_with_rng(node_id, params, &s_i, rng).unwrap();
+    (node, s_i)
+}
+
+/// Keys of a [`test_node`] with [`Params::TEST`]
+#[cfg(test)]
+pub(crate) fn test_key_pair(
+    rng: &mut ChaCha20Rng,
+) -> (CertificatelessQcMdpcPrivateKey, CertificatelessQcMdpcPublicKey) {
+    let (node, _) = test_node(1, &Params::TEST, rng);
+    let (public_key, _) = node.public_key_and_witness().unwrap();
+    (node.private_key(), public_key)
+}
+
+/// Registry bootstrapped by node 1, which is returned along with it
+#[cfg(test)]
+pub(crate) fn test_registry(
+    params: &Params,
+    rng: &mut ChaCha20Rng,
+) -> (MembershipRegistry, CertificatelessQcMdpc) {
+    let mut registry = MembershipRegistry::new(params).unwrap();
+    let (node, s_i) = test_node(1, params, rng);
+    let (public_key, witness) = node.public_key_and_witness().unwrap();
+    registry
+        .add_founding_member(1, s_i, public_key, witness)
+        .unwrap();
+    (registry, node)
+}
+
+/// Publishes the share binding of `voter` for its vote for `candidate_id`, then adds the vote
+#[cfg(test)]
+pub(crate) fn test_vote(
+    registry: &mut MembershipRegistry,
+    voter: &CertificatelessQcMdpc,
+    candidate_id: usize,
+) -> Result<(), Error> {
+    let signature = voter.accept_new_node(candidate_id);
+    registry.add_share_binding(
+        candidate_id,
+        signature.signing_node_id(),
+        signature.share_binding(candidate_id),
+    )?;
+    registry.add_vote(candidate_id, signature)
+}
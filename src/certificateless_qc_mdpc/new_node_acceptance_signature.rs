@@ -1,48 +1,140 @@
+use crate::binary_matrix_operations::matrix_vector_product;
+use crate::bit_vector::BitVector;
+use crate::certificateless_qc_mdpc::armor::{armor, dearmor, dearmor_expecting};
+use crate::certificateless_qc_mdpc::wire_format::{ObjectType, WireReader, WireWriter};
 use crate::certificateless_qc_mdpc::{
     generate_hash_id_vector_correct_weight, NodeWitnessSigPubKey,
 };
-use crate::my_bool::MyBool;
-use nalgebra::DMatrix;
-use num::{One, Zero};
-use num_bigint::BigInt;
-use crate::{SIGNATURE_WEIGHT_INTERVAL, SIG_K};
+use num_bigint::{BigInt, Sign};
+use sha3::{Digest, Sha3_256};
+use crate::{Error, Params};
+use std::str::FromStr;
+
+const ARMOR_LABEL: &str = "ACCEPTANCE SIGNATURE";
+const SHARE_BINDING_DOMAIN: &[u8] = b"democratic_pq_cle share binding v1";
+
+/// Size in bytes of [`NewNodeAcceptanceSignature::share_binding`]
+pub const SHARE_BINDING_SIZE: usize = 32;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NewNodeAcceptanceSignature {
     pub(super) signing_node_id: usize,
-    pub(super) signature: DMatrix<MyBool>,
+    pub(super) signature: BitVector,
 }
 
 impl NewNodeAcceptanceSignature {
-    #[allow(non_snake_case)]
-    pub fn is_valid(&self, signer_node_witness: &NodeWitnessSigPubKey, new_node_id: usize) -> bool {
-        let h_other_1 = generate_hash_id_vector_correct_weight(new_node_id, SIG_K, SIG_K >> 1);
-        let H_other_1: DMatrix<MyBool> = DMatrix::from_column_slice(1, SIG_K, &h_other_1);
+    pub fn is_valid(
+        &self,
+        signer_node_witness: &NodeWitnessSigPubKey,
+        new_node_id: usize,
+        params: &Params,
+    ) -> bool {
+        self.verify(signer_node_witness, new_node_id, params).is_ok()
+    }
 
-        let signature_weight = self.signature.row(0).iter().filter(|x| ***x).count();
+    /// Same as [`Self::is_valid`], telling why the signature is rejected
+    pub fn verify(
+        &self,
+        signer_node_witness: &NodeWitnessSigPubKey,
+        new_node_id: usize,
+        params: &Params,
+    ) -> Result<(), Error> {
+        signer_node_witness.verify(params)?;
+        let parity_matrix = &signer_node_witness.signature_parity_matrix;
+        let multiplication_matrix = &signer_node_witness.signature_multiplication_matrix;
+        if self.signature.len() != params.sig_n {
+            return Err(Error::InvalidLength {
+                expected: params.sig_n,
+                actual: self.signature.len(),
+            });
+        }
 
-        if signature_weight < SIGNATURE_WEIGHT_INTERVAL[0]
-            || signature_weight > SIGNATURE_WEIGHT_INTERVAL[1]
+        let h_other_1 =
+            generate_hash_id_vector_correct_weight(new_node_id, params.sig_k, params.sig_k >> 1);
+
+        let signature_weight = self.signature.weight();
+
+        if signature_weight < params.signature_weight_interval[0]
+            || signature_weight > params.signature_weight_interval[1]
         {
-            println!("Wrong signature weight: {}", signature_weight);
-            return false;
+            return Err(Error::InvalidSignatureWeight(signature_weight));
         }
 
-        signer_node_witness.signature_multiplication_matrix.clone() * H_other_1.transpose()
-            == signer_node_witness.signature_parity_matrix.clone() * self.signature.transpose()
+        if matrix_vector_product(multiplication_matrix, &h_other_1)
+            != matrix_vector_product(parity_matrix, &self.signature)
+        {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    /// Identifier of the member who voted
+    pub fn signing_node_id(&self) -> usize {
+        self.signing_node_id
+    }
+
+    /// `signing_node_id (u64 LE) || signature`, after the wire format header
+    pub fn to_bytes(&self, params: &Params) -> Vec<u8> {
+        let mut writer = WireWriter::new(ObjectType::Signature, params);
+        writer.write_u64(self.signing_node_id as u64);
+        writer.write_bits(&self.signature);
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params) -> Result<Self, Error> {
+        let mut reader = WireReader::new(bytes, ObjectType::Signature, params)?;
+        let signing_node_id = usize::try_from(reader.read_u64()?)
+            .map_err(|_| Error::InvalidEncoding("node identifier out of range"))?;
+        let signature = reader.read_bits(params.sig_n)?;
+        reader.finish()?;
+        Ok(Self {
+            signing_node_id,
+            signature,
+        })
+    }
+
+    /// PEM-like text encoding of [`Self::to_bytes`], parsed back with `FromStr`
+    pub fn to_armored(&self, params: &Params) -> String {
+        armor(ARMOR_LABEL, params, &self.to_bytes(params))
+    }
+
+    /// Parses the output of [`Self::to_armored`], failing unless it was written with `params`.
+    /// `FromStr` trusts the parameter set written in the text instead.
+    pub fn from_armored(text: &str, params: &Params) -> Result<Self, Error> {
+        let bytes = dearmor_expecting(text, ARMOR_LABEL, ObjectType::Signature, params)?;
+        Self::from_bytes(&bytes, params)
+    }
+
+    /// Hash binding the signer to the Shamir share of this vote for `candidate_id`, which the
+    /// signer publishes when the candidate is announced, before sending the vote to the candidate:
+    /// `SHA3-256("democratic_pq_cle share binding v1" || candidate_id || signing_node_id ||
+    /// signature)`, the identifiers being encoded as u64 LE. The signature bytes are the share
+    /// value of [`Self::to_shamir_share`] in little endian. It only ties the signer to the share it
+    /// sent: unlike a verifiable secret sharing commitment, it doesn't show that the shares are
+    /// consistent with each other.
+    pub fn share_binding(&self, candidate_id: usize) -> [u8; SHARE_BINDING_SIZE] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(SHARE_BINDING_DOMAIN);
+        hasher.update((candidate_id as u64).to_le_bytes());
+        hasher.update((self.signing_node_id as u64).to_le_bytes());
+        hasher.update(self.signature.to_bytes());
+        hasher.finalize().into()
     }
 
     pub fn to_shamir_share(&self) -> (usize, BigInt) {
-        let mut share_eval = BigInt::zero();
-        let mut pos_counter = 0usize;
-        for row in 0..self.signature.nrows() {
-            for col in 0..self.signature.ncols() {
-                if **self.signature.get((row, col)).unwrap() {
-                    share_eval += BigInt::one() << pos_counter;
-                }
-                pos_counter += 1;
-            }
-        }
+        // Signature bit i is the coefficient of 2^i
+        let share_eval = BigInt::from_bytes_le(Sign::Plus, &self.signature.to_bytes());
         (self.signing_node_id, share_eval)
     }
 }
+
+impl FromStr for NewNodeAcceptanceSignature {
+    type Err = Error;
+
+    /// Parses the output of [`NewNodeAcceptanceSignature::to_armored`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (params, bytes) = dearmor(s, ARMOR_LABEL)?;
+        Self::from_bytes(&bytes, &params)
+    }
+}
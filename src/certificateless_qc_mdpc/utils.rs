@@ -1,34 +1,81 @@
-use crate::my_bool::MyBool;
+use crate::bit_vector::BitVector;
+use crate::Error;
 use binary_polynomial_mod_algebra::{BinaryPolynomial, NonZeroBinaryPolynomial};
 use num::One;
 use num_bigint::BigUint;
-use rand::Rng;
 use rand_chacha::ChaCha20Rng;
-use rand_core::SeedableRng;
-use sha3::{Digest, Sha3_512};
+use rand_core::{CryptoRng, RngCore, SeedableRng};
+use sha3::digest::{ExtendableOutput, XofReader};
+use sha3::Shake256;
+use zeroize::Zeroize;
 
-pub(super) fn generate_random_weight_vector(size: usize, weight: usize) -> Vec<MyBool> {
-    let mut rng = ChaCha20Rng::from_entropy();
-    let mut secret_vector = vec![MyBool::from(false); size];
+/// Vector of length `size` and weight `weight`, see [`sample_weight_vector`]
+pub(super) fn generate_random_weight_vector<R: RngCore + CryptoRng>(
+    size: usize,
+    weight: usize,
+    rng: &mut R,
+) -> BitVector {
+    sample_weight_vector(size, weight, &mut |bytes| rng.fill_bytes(bytes))
+}
+
+/// Uniform integer below `bound`: `ceil(bits / 8)` bytes of `rng` read as a little-endian integer,
+/// `bits` being the length of `bound - 1`, with the bits above `bits` cleared, and rejected unless
+/// below `bound`. Like [`sample_weight_vector`], it only depends on the bytes read.
+pub(super) fn generate_random_biguint_below<R: RngCore + CryptoRng>(
+    bound: &BigUint,
+    rng: &mut R,
+) -> BigUint {
+    let bits = (bound - 1u8).bits();
+    let mut bytes = vec![0u8; bits.div_ceil(8) as usize];
+    loop {
+        rng.fill_bytes(&mut bytes);
+        if let Some(last) = bytes.last_mut() {
+            *last &= u8::MAX >> ((8 - bits % 8) % 8);
+        }
+        let candidate = BigUint::from_bytes_le(&bytes);
+        if &candidate < bound {
+            bytes.zeroize();
+            return candidate;
+        }
+    }
+}
+
+/// Vector of length `size` and weight `weight`, whose positions are drawn one at a time: 4 bytes
+/// from `read` as a u32 LE, masked with the smallest all-ones mask covering `size - 1`, and
+/// rejected if not below `size` or already set. Only the bytes read matter, so that seeded
+/// derivations don't depend on the version of `rand`.
+fn sample_weight_vector(
+    size: usize,
+    weight: usize,
+    read: &mut impl FnMut(&mut [u8]),
+) -> BitVector {
+    // At least half the samples are accepted
+    let index_mask = u32::MAX >> ((size - 1) as u32).leading_zeros().min(u32::BITS - 1);
+    let mut vector = BitVector::zeros(size);
     let mut current_weight = 0usize;
+    let mut sample = [0u8; 4];
     while current_weight < weight {
-        let idx = rng.gen_range(0..size);
-        if !*secret_vector[idx] {
-            secret_vector[idx] = MyBool::from(true);
+        read(&mut sample);
+        let idx = (u32::from_le_bytes(sample) & index_mask) as usize;
+        if idx < size && !vector.get(idx) {
+            vector.set(idx, true);
             current_weight += 1;
         }
     }
-    secret_vector
+    sample.zeroize();
+    vector
 }
 
 pub(super) fn check_vector_leads_to_invertible_circulant_matrix(
-    vector: &[MyBool],
+    vector: &BitVector,
     p: usize,
 ) -> bool {
-    let poly_vec = NonZeroBinaryPolynomial::new(BinaryPolynomial::from(
-        vector.iter().map(|x| **x).rev().collect::<Vec<bool>>(),
-    ))
-    .unwrap();
+    let poly_vec = match NonZeroBinaryPolynomial::new(BinaryPolynomial::from(
+        vector.iter().rev().collect::<Vec<bool>>(),
+    )) {
+        None => return false,
+        Some(poly_vec) => poly_vec,
+    };
     let modulus = compute_polynomial_modulus(p);
     poly_vec.inv_mod(&modulus).is_some()
 }
@@ -36,73 +83,221 @@ pub(super) fn check_vector_leads_to_invertible_circulant_matrix(
 pub fn generate_random_weight_vector_to_invertible_matrix(
     size: usize,
     weight: usize,
-) -> Vec<MyBool> {
-    let mut vector = generate_random_weight_vector(size, weight);
-    //vector.iter().for_each(|x| print!("{} ", x));
-    //println!("");
-    //println!("{:?}", vector);
+) -> BitVector {
+    generate_random_weight_vector_to_invertible_matrix_with_rng(
+        size,
+        weight,
+        &mut ChaCha20Rng::from_entropy(),
+    )
+}
+
+pub fn generate_random_weight_vector_to_invertible_matrix_with_rng<R: RngCore + CryptoRng>(
+    size: usize,
+    weight: usize,
+    rng: &mut R,
+) -> BitVector {
+    let mut vector = generate_random_weight_vector(size, weight, rng);
     while !check_vector_leads_to_invertible_circulant_matrix(&vector, size) {
-        println!("Regenerating vector");
-        vector = generate_random_weight_vector(size, weight);
-        while vector[(size >> 1)..size].iter().filter(|b| ***b).count() % 2 == 0 {
-            println!("Regenerating vector bis");
-            vector = generate_random_weight_vector(size, weight);
+        vector = generate_random_weight_vector(size, weight, rng);
+        while vector.slice((size >> 1)..size).weight().is_multiple_of(2) {
+            vector = generate_random_weight_vector(size, weight, rng);
         }
     }
     vector
 }
 
+/// Vector of length `k` and weight exactly `weight` derived from the identifier, see
+/// [`generate_seeded_weight_vector`]
 pub(super) fn generate_hash_id_vector_correct_weight(
     id: usize,
     k: usize,
     weight: usize,
-) -> Vec<MyBool> {
-    let mut hasher = Sha3_512::new();
-    hasher.update(id.to_string());
-    let h_id = hasher.finalize().as_slice().to_vec();
-    assert!(h_id.iter().map(|x| x.count_ones()).sum::<u32>() >= weight as u32);
-    let mut h_i_1 = vec![MyBool::from(false); k];
-    let mut current_weight = 0;
-    for i in 0..k {
-        if h_id[i >> 3] & (1 << (i & 7)) != 0 {
-            h_i_1[i] = MyBool::from(true);
-            current_weight += 1;
+) -> BitVector {
+    generate_seeded_weight_vector(id.to_string().as_bytes(), k, weight)
+}
+
+/// Upper bound on the re-derivations below, which are only a fallback: the parameters accepted by
+/// [`crate::Params::validate`] make the vectors they derive invertible.
+const INVERTIBILITY_ATTEMPTS: usize = 64;
+
+/// `h_i_1` of the node: [`generate_hash_id_vector_correct_weight`] of the identifier, or if it
+/// isn't invertible, the hash of `"<id>:<counter>"` for the first counter = 1, 2, ... giving an
+/// invertible vector. It only depends on public data, so every node finds the same `h_i_1`.
+pub(super) fn generate_invertible_hash_id_vector(
+    id: usize,
+    p: usize,
+    weight: usize,
+) -> Result<BitVector, Error> {
+    for counter in 0..INVERTIBILITY_ATTEMPTS {
+        let h_i_1 = if counter == 0 {
+            generate_hash_id_vector_correct_weight(id, p, weight)
+        } else {
+            generate_seeded_weight_vector(format!("{}:{}", id, counter).as_bytes(), p, weight)
+        };
+        if check_vector_leads_to_invertible_circulant_matrix(&h_i_1, p) {
+            return Ok(h_i_1);
         }
-        if current_weight == weight {
-            break;
+    }
+    Err(Error::NonInvertibleVector)
+}
+
+/// Same as [`generate_invertible_hash_id_vector`] for `s_i`, the retries being sampled from
+/// SHAKE256(`s_i` || counter (u32 LE))
+pub(super) fn derive_invertible_s_i(s_i: &BitVector, weight: usize) -> Result<BitVector, Error> {
+    let p = s_i.len();
+    if check_vector_leads_to_invertible_circulant_matrix(s_i, p) {
+        return Ok(s_i.clone());
+    }
+    let mut seed = s_i.to_bytes();
+    let s_i_len = seed.len();
+    for counter in 1..INVERTIBILITY_ATTEMPTS as u32 {
+        seed.truncate(s_i_len);
+        seed.extend_from_slice(&counter.to_le_bytes());
+        let derived = generate_seeded_weight_vector(&seed, p, weight);
+        if check_vector_leads_to_invertible_circulant_matrix(&derived, p) {
+            return Ok(derived);
         }
     }
-    h_i_1
+    Err(Error::NonInvertibleVector)
 }
 
-pub(super) fn try_invert_matrix_vector(matrix_first_line: &[MyBool]) -> Option<Vec<MyBool>> {
+/// Deterministic vector of length `size` and weight `weight`, sampled from SHAKE256(seed) with
+/// [`sample_weight_vector`]
+pub(super) fn generate_seeded_weight_vector(seed: &[u8], size: usize, weight: usize) -> BitVector {
+    let mut hasher = Shake256::default();
+    sha3::digest::Update::update(&mut hasher, seed);
+    let mut reader = hasher.finalize_xof();
+    sample_weight_vector(size, weight, &mut |bytes| reader.read(bytes))
+}
+
+/// Size in bytes of the length prefix of framed messages
+pub(super) const MESSAGE_LENGTH_PREFIX_SIZE: usize = 4;
+
+/// `data length (u32 LE) || data || zero padding`, on `size` bits
+pub(super) fn frame_message(data: &[u8], size: usize) -> BitVector {
+    assert!(MESSAGE_LENGTH_PREFIX_SIZE + data.len() <= size >> 3);
+    let mut framed = Vec::with_capacity(MESSAGE_LENGTH_PREFIX_SIZE + data.len());
+    framed.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    framed.extend_from_slice(data);
+    BitVector::from_bytes(&framed, size)
+}
+
+/// Inverse of [`frame_message`]
+pub(super) fn unframe_message(framed: &BitVector) -> Result<Vec<u8>, Error> {
+    let bytes = framed.to_bytes();
+    if bytes.len() < MESSAGE_LENGTH_PREFIX_SIZE {
+        return Err(Error::InvalidCiphertext);
+    }
+    let data_len =
+        u32::from_le_bytes(bytes[..MESSAGE_LENGTH_PREFIX_SIZE].try_into().unwrap()) as usize;
+    let data_end = MESSAGE_LENGTH_PREFIX_SIZE + data_len;
+    if data_end > framed.len() >> 3 || bytes[data_end..].iter().any(|&byte| byte != 0) {
+        return Err(Error::InvalidCiphertext);
+    }
+    Ok(bytes[MESSAGE_LENGTH_PREFIX_SIZE..data_end].to_vec())
+}
+
+pub(super) fn try_invert_matrix_vector(matrix_first_line: &BitVector) -> Option<BitVector> {
     let matrix_size = matrix_first_line.len();
     let modulus = compute_polynomial_modulus(matrix_size);
     let polynomial = NonZeroBinaryPolynomial::new(BinaryPolynomial::from(
-        matrix_first_line.iter().map(|x| **x).rev().collect::<Vec<bool>>(),
+        matrix_first_line.iter().rev().collect::<Vec<bool>>(),
     ));
-    let inverse = polynomial.unwrap().inv_mod(&modulus)?;
+    let inverse = polynomial?.inv_mod(&modulus)?;
     let inverse_vector: Vec<bool> = inverse.into();
-    let result: Vec<MyBool> = inverse_vector.iter().map(|x| MyBool::from(*x)).rev().collect();
-    let result_len = result.len();
-    let result = [result, vec![MyBool::from(false); matrix_size - result_len]].concat();
-    Some(result)
+    Some(polynomial_coefficients_to_vector(&inverse_vector, matrix_size))
 }
 
-pub(super) fn multiply_2_matrix_first_line_vector(first_line_matrix1: &[MyBool], first_line_matrix2: &[MyBool]) -> Vec<MyBool> {
+/// Product of the two polynomials in GF(2)[x]/(x^p - 1), which is also the first line of the
+/// product of the two circulant matrices
+pub(super) fn multiply_2_matrix_first_line_vector(first_line_matrix1: &BitVector, first_line_matrix2: &BitVector) -> BitVector {
     assert_eq!(first_line_matrix1.len(), first_line_matrix2.len());
-    let matrix_size = first_line_matrix1.len();
-    let modulus = compute_polynomial_modulus(matrix_size);
-    let polynomial1 = BinaryPolynomial::from(first_line_matrix1.iter().map(|x| **x).rev().collect::<Vec<bool>>());
-    let polynomial2 = BinaryPolynomial::from(first_line_matrix2.iter().map(|x| **x).rev().collect::<Vec<bool>>());
-    let multiply_vec: Vec<bool> = polynomial1.mul_mod(&polynomial2, &modulus).unwrap().into();
-    let result: Vec<MyBool> = multiply_vec.iter().map(|x| MyBool::from(*x)).rev().collect();
-    let result_len = result.len();
-    [result, vec![MyBool::from(false); matrix_size - result_len]].concat()
+    let (sparse, dense) = if first_line_matrix1.weight() <= first_line_matrix2.weight() {
+        (first_line_matrix1, first_line_matrix2)
+    } else {
+        (first_line_matrix2, first_line_matrix1)
+    };
+    let mut result = BitVector::zeros(dense.len());
+    for shift in sparse.ones() {
+        result ^= &dense.rotate_right(shift);
+    }
+    result
+}
+
+/// First line of the transpose of the circulant matrix
+pub(super) fn transpose_matrix_first_line_vector(matrix_first_line: &BitVector) -> BitVector {
+    let matrix_size = matrix_first_line.len();
+    (0..matrix_size)
+        .map(|i| matrix_first_line.get((matrix_size - i) % matrix_size))
+        .collect()
+}
+
+/// Polynomial coefficients are given from the highest degree, the vector is padded with zeros up to `size`
+fn polynomial_coefficients_to_vector(coefficients: &[bool], size: usize) -> BitVector {
+    let vector: BitVector = coefficients.iter().rev().copied().collect();
+    let vector_len = vector.len();
+    vector.concat(&BitVector::zeros(size - vector_len))
 }
 
 fn compute_polynomial_modulus(matrix_size: usize) -> NonZeroBinaryPolynomial {
     let mut modulus_biguint = BigUint::one();
     modulus_biguint.set_bit(matrix_size as u64, true);
     NonZeroBinaryPolynomial::new(BinaryPolynomial::from(modulus_biguint)).unwrap()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_vector_leads_to_invertible_circulant_matrix, derive_invertible_s_i,
+        generate_hash_id_vector_correct_weight, generate_invertible_hash_id_vector,
+    };
+    use crate::Error;
+    use crate::bit_vector::BitVector;
+
+    #[test]
+    fn test_derive_invertible_s_i() {
+        // x^11 - 1 = (x + 1)(x^10 + ... + 1): odd weight vectors other than the all-ones one are
+        // invertible
+        let invertible: BitVector = (0..11).map(|i| i < 3).collect();
+        assert_eq!(derive_invertible_s_i(&invertible, 3), Ok(invertible));
+
+        let even_weight: BitVector = (0..11).map(|i| i < 2).collect();
+        let derived = derive_invertible_s_i(&even_weight, 3).unwrap();
+        assert_eq!(derived.weight(), 3);
+        assert!(check_vector_leads_to_invertible_circulant_matrix(&derived, 11));
+        assert_eq!(derive_invertible_s_i(&even_weight, 3), Ok(derived));
+    }
+
+    #[test]
+    fn test_generate_invertible_hash_id_vector() {
+        // x^7 - 1 = (x + 1)(x^3 + x + 1)(x^3 + x^2 + 1): 14 of the 35 weight 3 vectors are
+        // multiples of a degree 3 factor
+        let id = (0..)
+            .find(|&id| {
+                let hash = generate_hash_id_vector_correct_weight(id, 7, 3);
+                !check_vector_leads_to_invertible_circulant_matrix(&hash, 7)
+            })
+            .unwrap();
+        let h_i_1 = generate_invertible_hash_id_vector(id, 7, 3).unwrap();
+        assert_eq!(h_i_1.weight(), 3);
+        assert!(check_vector_leads_to_invertible_circulant_matrix(&h_i_1, 7));
+        assert_eq!(generate_invertible_hash_id_vector(id, 7, 3), Ok(h_i_1));
+
+        // Even weight vectors are multiples of x + 1
+        assert_eq!(
+            generate_invertible_hash_id_vector(id, 11, 2),
+            Err(Error::NonInvertibleVector)
+        );
+    }
+
+    #[test]
+    fn test_generate_hash_id_vector_correct_weight() {
+        // Votes take half the bits, more than a fixed-size hash could provide for large sig_k
+        for k in [27, 160, 343, 1000] {
+            for id in 0..20 {
+                let vector = generate_hash_id_vector_correct_weight(id, k, k >> 1);
+                assert_eq!((vector.len(), vector.weight()), (k, k >> 1));
+                assert_eq!(generate_hash_id_vector_correct_weight(id, k, k >> 1), vector);
+            }
+        }
+    }
+}
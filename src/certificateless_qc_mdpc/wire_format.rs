@@ -0,0 +1,233 @@
+use crate::bit_vector::BitVector;
+use crate::my_bool::MyBool;
+use crate::params::PARAMS_IDENTIFIER_SIZE;
+use crate::{Error, Params};
+use nalgebra::DMatrix;
+
+/// Version of the encoding shared by all the objects below
+const WIRE_FORMAT_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 2 + PARAMS_IDENTIFIER_SIZE;
+
+/// Type byte of the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(super) enum ObjectType {
+    PublicKey = 1,
+    Witness = 2,
+    Signature = 3,
+    KemCiphertext = 4,
+    HybridCiphertext = 5,
+    PrivateKey = 6,
+}
+
+impl ObjectType {
+    /// Identifier of the parameters the object depends on: [`Params::code_identifier`] for
+    /// encryption keys and ciphertexts, [`Params::identifier`] for witnesses and signatures
+    pub(super) fn params_identifier(self, params: &Params) -> [u8; PARAMS_IDENTIFIER_SIZE] {
+        match self {
+            ObjectType::Witness | ObjectType::Signature => params.identifier(),
+            ObjectType::PublicKey
+            | ObjectType::KemCiphertext
+            | ObjectType::HybridCiphertext
+            | ObjectType::PrivateKey => params.code_identifier(),
+        }
+    }
+}
+
+/// Every encoded object starts with `version (1 byte) || object type (1 byte) || parameter set
+/// identifier` (see [`ObjectType::params_identifier`]), followed by its fields. Bit vectors and matrices (row by row) are packed LSB first,
+/// padded with zeros to a whole number of bytes. Their size is given by the parameter set, so it
+/// isn't encoded.
+pub(super) struct WireWriter {
+    bytes: Vec<u8>,
+}
+
+impl WireWriter {
+    pub(super) fn new(object_type: ObjectType, params: &Params) -> Self {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.push(WIRE_FORMAT_VERSION);
+        bytes.push(object_type as u8);
+        bytes.extend_from_slice(&object_type.params_identifier(params));
+        Self { bytes }
+    }
+
+    pub(super) fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(super) fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(super) fn write_bits(&mut self, bits: &BitVector) {
+        self.bytes.extend_from_slice(&bits.to_bytes());
+    }
+
+    pub(super) fn write_matrix(&mut self, matrix: &DMatrix<MyBool>) {
+        let bits: BitVector = (0..matrix.nrows())
+            .flat_map(|row| (0..matrix.ncols()).map(move |col| *matrix[(row, col)]))
+            .collect();
+        self.write_bits(&bits);
+    }
+
+    pub(super) fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Strict reader for [`WireWriter`]'s output: fails on any unexpected header, size or padding, and
+/// on parameter sets [`Params::validate`] rejects, as sizes are derived from them
+pub(super) struct WireReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> WireReader<'a> {
+    pub(super) fn new(
+        bytes: &'a [u8],
+        object_type: ObjectType,
+        params: &Params,
+    ) -> Result<Self, Error> {
+        params.validate()?;
+        let mut reader = Self { bytes, position: 0 };
+        let header = reader.read_bytes(HEADER_SIZE)?;
+        if header[0] != WIRE_FORMAT_VERSION {
+            return Err(Error::InvalidEncoding("unsupported wire format version"));
+        }
+        if header[1] != object_type as u8 {
+            return Err(Error::InvalidEncoding("unexpected object type"));
+        }
+        if header[2..] != object_type.params_identifier(params) {
+            return Err(Error::ParametersMismatch);
+        }
+        Ok(reader)
+    }
+
+    pub(super) fn read_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    pub(super) fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    pub(super) fn read_bits(&mut self, len: usize) -> Result<BitVector, Error> {
+        let bytes = self.read_bytes(len.div_ceil(8))?;
+        let bits = BitVector::from_bytes(bytes, len);
+        if bits.to_bytes() != bytes {
+            return Err(Error::InvalidEncoding("non-zero padding bits"));
+        }
+        Ok(bits)
+    }
+
+    pub(super) fn read_matrix(
+        &mut self,
+        rows: usize,
+        cols: usize,
+    ) -> Result<DMatrix<MyBool>, Error> {
+        let len = rows
+            .checked_mul(cols)
+            .ok_or(Error::InvalidEncoding("matrix too large"))?;
+        let bits = self.read_bits(len)?;
+        Ok(DMatrix::from_fn(rows, cols, |row, col| {
+            MyBool::from(bits.get(row * cols + col))
+        }))
+    }
+
+    /// All the remaining bytes
+    pub(super) fn read_remaining(&mut self) -> &'a [u8] {
+        let remaining = &self.bytes[self.position..];
+        self.position = self.bytes.len();
+        remaining
+    }
+
+    /// Fails if some bytes haven't been read
+    pub(super) fn finish(self) -> Result<(), Error> {
+        if self.position != self.bytes.len() {
+            return Err(Error::InvalidLength {
+                expected: self.position,
+                actual: self.bytes.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .position
+            .checked_add(len)
+            .ok_or(Error::InvalidEncoding("length too large"))?;
+        if end > self.bytes.len() {
+            return Err(Error::InvalidLength {
+                expected: end,
+                actual: self.bytes.len(),
+            });
+        }
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ObjectType, WireReader, WireWriter};
+    use crate::bit_vector::BitVector;
+    use crate::my_bool::MyBool;
+    use crate::{Error, Params};
+    use nalgebra::DMatrix;
+
+    #[test]
+    fn test_wire_format() {
+        let params = Params::default();
+        let bits: BitVector = [true, false, true, true, false, false, true, false, true]
+            .into_iter()
+            .collect();
+        let matrix = DMatrix::from_fn(3, 5, |row, col| MyBool::from((row + col) % 3 == 0));
+        let mut writer = WireWriter::new(ObjectType::Signature, &params);
+        writer.write_u64(42);
+        writer.write_bits(&bits);
+        writer.write_matrix(&matrix);
+        let bytes = writer.finish();
+
+        let mut reader = WireReader::new(&bytes, ObjectType::Signature, &params).unwrap();
+        assert_eq!(reader.read_u64(), Ok(42));
+        assert_eq!(reader.read_bits(bits.len()), Ok(bits.clone()));
+        assert_eq!(reader.read_matrix(3, 5), Ok(matrix));
+        assert_eq!(reader.finish(), Ok(()));
+
+        assert!(WireReader::new(&bytes, ObjectType::Witness, &params).is_err());
+        let other_params = Params {
+            t: params.t + 1,
+            ..params
+        };
+        assert_eq!(
+            WireReader::new(&bytes, ObjectType::Signature, &other_params).err(),
+            Some(Error::ParametersMismatch)
+        );
+
+        let mut reader = WireReader::new(&bytes, ObjectType::Signature, &params).unwrap();
+        reader.read_u64().unwrap();
+        assert!(reader.read_bits(bits.len()).is_ok());
+        assert!(reader.finish().is_err());
+        let mut writer = WireWriter::new(ObjectType::Signature, &params);
+        writer.write_bytes(&[0xff]);
+        let bytes_with_padding = writer.finish();
+        let mut reader =
+            WireReader::new(&bytes_with_padding, ObjectType::Signature, &params).unwrap();
+        assert!(reader.read_bits(7).is_err());
+        let mut truncated = WireReader::new(&bytes[..12], ObjectType::Signature, &params).unwrap();
+        assert!(truncated.read_u64().is_err());
+        let mut reader = WireReader::new(&bytes, ObjectType::Signature, &params).unwrap();
+        assert!(reader.read_matrix(usize::MAX, 2).is_err());
+        assert!(reader.read_bits(usize::MAX).is_err());
+
+        // The sizes of the fields are derived from the parameters, which must be valid
+        let invalid_params = Params { p: 7, ..params };
+        let bytes = WireWriter::new(ObjectType::Signature, &invalid_params).finish();
+        assert!(matches!(
+            WireReader::new(&bytes, ObjectType::Signature, &invalid_params).err(),
+            Some(Error::InvalidParameters(_))
+        ));
+    }
+}
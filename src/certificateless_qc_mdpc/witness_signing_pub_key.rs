@@ -1,9 +1,135 @@
+use crate::binary_matrix_operations::{
+    concat_horizontally_mat, make_identity_matrix, make_truncated_circulant_matrix,
+};
+use crate::bit_vector::BitVector;
+use crate::certificateless_qc_mdpc::armor::{armor, dearmor, dearmor_expecting};
+use crate::certificateless_qc_mdpc::wire_format::{ObjectType, WireReader, WireWriter};
 use crate::my_bool::MyBool;
+use crate::{Error, Params};
 use nalgebra::DMatrix;
+use std::str::FromStr;
+
+const ARMOR_LABEL: &str = "NODE WITNESS";
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "WitnessFields"))]
 pub struct NodeWitnessSigPubKey {
-    pub pubkey_witness_vector: Vec<MyBool>,
+    pub pubkey_witness_vector: BitVector,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::packed_matrix"))]
     pub signature_parity_matrix: DMatrix<MyBool>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::packed_matrix"))]
     pub signature_multiplication_matrix: DMatrix<MyBool>,
 }
+
+impl NodeWitnessSigPubKey {
+    /// `pubkey_witness_vector || signature_multiplication_matrix`, after the wire format header.
+    /// The signature parity-check matrix is derived from the witness vector, so it isn't encoded.
+    pub fn to_bytes(&self, params: &Params) -> Vec<u8> {
+        let mut writer = WireWriter::new(ObjectType::Witness, params);
+        writer.write_bits(&self.pubkey_witness_vector);
+        writer.write_matrix(&self.signature_multiplication_matrix);
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: &Params) -> Result<Self, Error> {
+        let mut reader = WireReader::new(bytes, ObjectType::Witness, params)?;
+        let pubkey_witness_vector = reader.read_bits(params.p)?;
+        let signature_multiplication_matrix = reader.read_matrix(params.sig_r, params.sig_k)?;
+        reader.finish()?;
+        Ok(Self {
+            signature_parity_matrix: make_signature_parity_matrix(&pubkey_witness_vector, params),
+            pubkey_witness_vector,
+            signature_multiplication_matrix,
+        })
+    }
+
+    /// PEM-like text encoding of [`Self::to_bytes`], parsed back with `FromStr`
+    pub fn to_armored(&self, params: &Params) -> String {
+        armor(ARMOR_LABEL, params, &self.to_bytes(params))
+    }
+
+    /// Parses the output of [`Self::to_armored`], failing unless it was written with `params`.
+    /// `FromStr` trusts the parameter set written in the text instead.
+    pub fn from_armored(text: &str, params: &Params) -> Result<Self, Error> {
+        let bytes = dearmor_expecting(text, ARMOR_LABEL, ObjectType::Witness, params)?;
+        Self::from_bytes(&bytes, params)
+    }
+
+    /// Checks the sizes of the witness against `params`, and that the signature parity-check
+    /// matrix is the one derived from the witness vector
+    pub fn verify(&self, params: &Params) -> Result<(), Error> {
+        if self.pubkey_witness_vector.len() != params.p
+            || self.signature_parity_matrix.shape() != (params.sig_r, params.sig_n)
+            || self.signature_multiplication_matrix.shape() != (params.sig_r, params.sig_k)
+        {
+            return Err(Error::InvalidWitness);
+        }
+        self.check_consistency()
+    }
+
+    /// Checks that the signature parity-check matrix is the one derived from the witness vector,
+    /// and that both signature matrices have `sig_r` rows
+    fn check_consistency(&self) -> Result<(), Error> {
+        let (sig_r, sig_n) = self.signature_parity_matrix.shape();
+        if sig_r > sig_n || self.signature_multiplication_matrix.nrows() != sig_r {
+            return Err(Error::InvalidWitness);
+        }
+        if self.signature_parity_matrix
+            != signature_parity_matrix(&self.pubkey_witness_vector, sig_n, sig_r)
+        {
+            return Err(Error::InvalidWitness);
+        }
+        Ok(())
+    }
+}
+
+/// Deserialized form of [`NodeWitnessSigPubKey`], only accepted once
+/// [`NodeWitnessSigPubKey::check_consistency`] passes
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "NodeWitnessSigPubKey")]
+struct WitnessFields {
+    pubkey_witness_vector: BitVector,
+    #[serde(with = "crate::serde_support::packed_matrix")]
+    signature_parity_matrix: DMatrix<MyBool>,
+    #[serde(with = "crate::serde_support::packed_matrix")]
+    signature_multiplication_matrix: DMatrix<MyBool>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<WitnessFields> for NodeWitnessSigPubKey {
+    type Error = Error;
+
+    fn try_from(fields: WitnessFields) -> Result<Self, Self::Error> {
+        let witness = Self {
+            pubkey_witness_vector: fields.pubkey_witness_vector,
+            signature_parity_matrix: fields.signature_parity_matrix,
+            signature_multiplication_matrix: fields.signature_multiplication_matrix,
+        };
+        witness.check_consistency()?;
+        Ok(witness)
+    }
+}
+
+impl FromStr for NodeWitnessSigPubKey {
+    type Err = Error;
+
+    /// Parses the output of [`NodeWitnessSigPubKey::to_armored`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (params, bytes) = dearmor(s, ARMOR_LABEL)?;
+        Self::from_bytes(&bytes, &params)
+    }
+}
+
+/// `[I | R_i]`, `R_i` being the top-left corner of the circulant matrix of `r_i`
+pub(super) fn make_signature_parity_matrix(r_i: &BitVector, params: &Params) -> DMatrix<MyBool> {
+    signature_parity_matrix(r_i, params.sig_n, params.sig_r)
+}
+
+fn signature_parity_matrix(r_i: &BitVector, sig_n: usize, sig_r: usize) -> DMatrix<MyBool> {
+    let mut signature_parity_matrix = make_identity_matrix(sig_r);
+    let r_i_truncated = make_truncated_circulant_matrix(r_i, sig_r, sig_n - sig_r);
+    concat_horizontally_mat(&mut signature_parity_matrix, &r_i_truncated);
+    signature_parity_matrix
+}
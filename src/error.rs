@@ -0,0 +1,94 @@
+use std::fmt;
+
+/// Errors returned by the fallible functions of the crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The parameter set doesn't pass [`crate::Params::validate`]
+    InvalidParameters(&'static str),
+    /// The parameter set doesn't match the one of a key or of a backup
+    ParametersMismatch,
+    /// A vector or a byte string doesn't have the expected length
+    InvalidLength { expected: usize, actual: usize },
+    /// A vector doesn't have the expected Hamming weight
+    InvalidWeight { expected: usize, actual: usize },
+    /// A vector that must be invertible, as the first line of a circulant matrix, isn't
+    NonInvertibleVector,
+    /// The message is longer than what the public key can encrypt
+    MessageTooLong { max: usize, actual: usize },
+    /// The decoder couldn't find the error vector
+    DecodingFailure,
+    /// The ciphertext wasn't generated by an honest encryption
+    InvalidCiphertext,
+    /// The authenticated encryption layer rejected the ciphertext or the associated data
+    DecryptionFailure,
+    /// The public key doesn't match the witness, the node identifier and `s_i`
+    InvalidWitness,
+    /// The signature weight is out of `Params::signature_weight_interval`
+    InvalidSignatureWeight(usize),
+    /// The signature doesn't verify against the signer's witness
+    InvalidSignature,
+    /// A serialized value couldn't be parsed
+    InvalidEncoding(&'static str),
+    /// The node is already a member of the network
+    AlreadyMember(usize),
+    /// The node isn't a member of the network
+    UnknownMember(usize),
+    /// The member already voted, or published a binding to its vote, for this candidate
+    DuplicateVote(usize),
+    /// The candidate didn't get enough votes yet
+    NotEnoughVotes { needed: usize, actual: usize },
+    /// The candidate hasn't been admitted, so it can't be registered
+    NotAdmitted(usize),
+    /// The network already has a member, so nodes can only join through votes
+    AlreadyBootstrapped,
+    /// The member didn't publish a binding to its vote for this candidate
+    MissingShareBinding(usize),
+    /// The vote of the member doesn't match the binding it published
+    ShareBindingMismatch(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidParameters(reason) => write!(f, "Invalid parameters: {}", reason),
+            Error::ParametersMismatch => write!(f, "Parameters mismatch"),
+            Error::InvalidLength { expected, actual } => {
+                write!(f, "Invalid length: expected {}, got {}", expected, actual)
+            }
+            Error::InvalidWeight { expected, actual } => {
+                write!(f, "Invalid weight: expected {}, got {}", expected, actual)
+            }
+            Error::NonInvertibleVector => write!(f, "Non-invertible vector"),
+            Error::MessageTooLong { max, actual } => {
+                write!(f, "Message too long: {} bytes, at most {} allowed", actual, max)
+            }
+            Error::DecodingFailure => write!(f, "Decoding failed"),
+            Error::InvalidCiphertext => write!(f, "Invalid ciphertext"),
+            Error::DecryptionFailure => write!(f, "Decryption failed"),
+            Error::InvalidWitness => write!(f, "Invalid witness"),
+            Error::InvalidSignatureWeight(weight) => {
+                write!(f, "Invalid signature weight: {}", weight)
+            }
+            Error::InvalidSignature => write!(f, "Invalid signature"),
+            Error::InvalidEncoding(reason) => write!(f, "Invalid encoding: {}", reason),
+            Error::AlreadyMember(node_id) => write!(f, "Node {} is already a member", node_id),
+            Error::UnknownMember(node_id) => write!(f, "Node {} isn't a member", node_id),
+            Error::DuplicateVote(node_id) => write!(f, "Node {} already voted", node_id),
+            Error::NotEnoughVotes { needed, actual } => {
+                write!(f, "Not enough votes: {} out of {} needed", actual, needed)
+            }
+            Error::NotAdmitted(node_id) => write!(f, "Node {} hasn't been admitted", node_id),
+            Error::AlreadyBootstrapped => {
+                write!(f, "The network already has members, new nodes must be voted in")
+            }
+            Error::MissingShareBinding(node_id) => {
+                write!(f, "Node {} didn't publish a binding to its vote", node_id)
+            }
+            Error::ShareBindingMismatch(node_id) => {
+                write!(f, "The vote of node {} doesn't match its share binding", node_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
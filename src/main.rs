@@ -1,167 +1,159 @@
-use std::str::FromStr;
-use democratic_pq_cle::certificateless_qc_mdpc::CertificatelessQcMdpc;
-use democratic_pq_cle::math::{binom, nth_combination};
-use democratic_pq_cle::my_bool::MyBool;
+use democratic_pq_cle::certificateless_qc_mdpc::{
+    CertificatelessQcMdpc, CertificatelessQcMdpcPrivateKey, CertificatelessQcMdpcPublicKey,
+    DecodingAlgorithm, HybridCiphertext, MembershipRegistry, NewNodeAcceptanceSignature,
+    NodeWitnessSigPubKey,
+};
 use democratic_pq_cle::utils::generate_random_weight_vector_to_invertible_matrix;
-use num::integer::Roots;
-use num::Integer;
-use num_bigint::{BigUint, ToBigInt};
-use verifiable_secret_sharing::ShamirSecretSharing as SSS;
-use democratic_pq_cle::{P, T, VOTES_THRESHOLD, W};
+use democratic_pq_cle::Params;
 
 const MESSAGE: &'static str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWX";
-// Warning: Must regenerate prime when increasing P: size = P << 2 ??
-const SHAMIR_PRIME: &'static str = "160709158425158035654685227325973365624663273287406113461145953791824931969868553857443975598081782425782238924631575521690050079686432679571207930665013242855292363190357607432848330361342045009708758970141017149750410159672120775535180892519552660606472653252094488915011087690901235041024920903936951266492676478477152395959987044121055694487824454548513291118740413831170656966083481545846322276907730288640437837123252820411487356601254412916684662133890553145125006204300890659";
 
 fn main() {
     let start = std::time::Instant::now();
-    /*let prime = Generator::new_prime(P << 2);
-    println!("{}", prime);*/
-    let shamir_prime = BigUint::from_str(SHAMIR_PRIME).unwrap();
-    let shamir_prime = shamir_prime.to_bigint().unwrap();
+    let params = Params::default();
 
-    let si_weight = (W >> 1).nth_root(2);
-    let binomial_coef_s_i_generation = binom(P, si_weight).to_bigint().unwrap();
-
-    let mut nodes_currently_in_system_count = 0;
+    let si_weight = params.s_i_weight();
 
     // Init node 1, using a random s_i vector
 
-    let s_i_node_1 = generate_random_weight_vector_to_invertible_matrix(P, si_weight);
-    let node_1 = CertificatelessQcMdpc::init(1, P, W, T, &s_i_node_1);
-
-    nodes_currently_in_system_count += 1;
-    let (node_1_public_key, node_1_witness) = node_1.public_key_and_witness();
+    let s_i_node_1 = generate_random_weight_vector_to_invertible_matrix(params.p, si_weight);
+    let node_1 = CertificatelessQcMdpc::init(1, &params, &s_i_node_1).unwrap();
+    let (node_1_public_key, node_1_witness) = node_1.public_key_and_witness().unwrap();
+    // The public key and the witness are broadcast to the other nodes
+    let node_1_public_key = CertificatelessQcMdpcPublicKey::from_bytes(
+        &node_1_public_key.to_bytes(&params),
+        &params,
+    )
+    .unwrap();
+    let node_1_witness =
+        NodeWitnessSigPubKey::from_bytes(&node_1_witness.to_bytes(&params), &params).unwrap();
     println!(
         "Node 1: Public key verified: {}",
-        node_1_public_key.check_is_valid(1, &s_i_node_1, &node_1_witness, W)
+        node_1_public_key.check_is_valid(1, &s_i_node_1, &node_1_witness, &params)
+    );
+
+    let node_1_backup = node_1.backup().to_string();
+    println!("Node 1: Key backup: {}", node_1_backup);
+    let node_1_restored =
+        CertificatelessQcMdpc::restore(&node_1_backup.parse().unwrap(), &params, &s_i_node_1)
+            .unwrap();
+    println!(
+        "Node 1: Restored private key matches: {}",
+        node_1_restored.private_key() == node_1.private_key()
     );
 
-    let node_1_private_key = node_1.private_key();
-    let encrypted = node_1_public_key.encrypt(MESSAGE.as_bytes());
+    print!("{}", node_1_public_key.to_armored(&params));
+    let node_1_armored_private_key = node_1.private_key().to_armored(&params);
     println!(
-        "Encrypted: {}",
-        encrypted
-            .iter()
-            .map(|x| if **x { '1' } else { '0' })
-            .collect::<String>()
+        "Node 1: Armored private key parsed back: {}",
+        CertificatelessQcMdpcPrivateKey::from_armored(&node_1_armored_private_key, &params)
+            == Ok(node_1.private_key())
     );
 
+    let mut node_1_private_key = node_1.private_key();
+    node_1_private_key.set_decoding_algorithm(DecodingAlgorithm::BlackGrayFlip);
+    let encrypted = node_1_public_key.encrypt(MESSAGE.as_bytes()).unwrap();
+    println!("Encrypted: {}", encrypted);
+
     let decrypted = node_1_private_key
         .decrypt(&encrypted)
         .unwrap();
     println!(
         "Node 1: Decrypted data: {}",
-        std::str::from_utf8(&decrypted[0..MESSAGE.len()]).unwrap()
+        std::str::from_utf8(&decrypted).unwrap()
     );
 
-    // Node 1 accepts the new node 2
-
-    // The signature should be broadcast to all nodes, in order to allow all nodes to verify the new node initialization vector
-    let new_node_2_signature_from_node_1 = node_1.accept_new_node(2);
+    let error_vector = node_1_public_key.random_error_vector();
+    let syndrome = node_1_public_key.niederreiter_encrypt(&error_vector).unwrap();
     println!(
-        "New node 2 signature valid from node 1: {}",
-        new_node_2_signature_from_node_1.is_valid(&node_1_witness, 2)
+        "Node 1: Niederreiter error vector recovered: {}",
+        node_1_private_key.niederreiter_decrypt(&syndrome) == Ok(error_vector)
     );
 
-    let shamir_voting_threshold =
-        ((nodes_currently_in_system_count as f32) * VOTES_THRESHOLD).ceil() as usize;
+    let (kem_ciphertext, shared_secret) = node_1_public_key.encapsulate();
     println!(
-        "Accepting a new node... Voting threshold = {}",
-        shamir_voting_threshold
+        "Node 1: KEM shared secrets match: {}",
+        node_1_private_key.decapsulate(&kem_ciphertext) == Ok(shared_secret)
     );
-    let sss = SSS {
-        threshold: shamir_voting_threshold,
-        share_amount: 1,
-        prime: shamir_prime.clone(),
-    };
-    let s_node_2_combination_index = sss
-        .recover(&[new_node_2_signature_from_node_1.to_shamir_share()])
-        .mod_floor(&binomial_coef_s_i_generation);
-    let mut s_i_node_2 = vec![MyBool::from(false); P];
-    for index_to_flip in nth_combination(
-        P,
-        si_weight,
-        s_node_2_combination_index.to_biguint().unwrap(),
-    ) {
-        s_i_node_2[index_to_flip] = MyBool::from(true);
-    }
-    let node_2 = CertificatelessQcMdpc::init(2, P, W, T, &s_i_node_2);
-    nodes_currently_in_system_count += 1;
-    let (node_2_public_key, node_2_witness) = node_2.public_key_and_witness();
+
+    let long_message = MESSAGE.repeat(100);
+    let hybrid_ciphertext = node_1_public_key.hybrid_encrypt(long_message.as_bytes(), b"node 1");
+    let hybrid_ciphertext =
+        HybridCiphertext::from_bytes(&hybrid_ciphertext.to_bytes(&params), &params).unwrap();
     println!(
-        "Node 2: Public key verified: {}",
-        node_2_public_key.check_is_valid(2, &s_i_node_2, &node_2_witness, W)
+        "Node 1: Hybrid decryption of a {} bytes message matches: {}",
+        long_message.len(),
+        node_1_private_key.hybrid_decrypt(&hybrid_ciphertext, b"node 1")
+            == Ok(long_message.into_bytes())
     );
 
-    let node_2_private_key = node_2.private_key();
-    let encrypted = node_2_public_key.encrypt(MESSAGE.as_bytes());
-
-    let decrypted = node_2_private_key
-        .decrypt(&encrypted)
+    let mut registry = MembershipRegistry::new(&params).unwrap();
+    registry
+        .add_founding_member(1, s_i_node_1, node_1_public_key, node_1_witness)
         .unwrap();
-    println!(
-        "Node 2: Decrypted data: {}",
-        std::str::from_utf8(&decrypted[0..MESSAGE.len()]).unwrap()
-    );
 
-    // Node 1 and 2 accepts the new node 3
-    let new_node_3_signature_from_node_1 = node_1.accept_new_node(3);
-    let new_node_3_signature_from_node_2 = node_2.accept_new_node(3);
-    println!(
-        "New node 3 signature valid from node 1: {}",
-        new_node_3_signature_from_node_1.is_valid(&node_1_witness, 3)
-    );
+    // The signature should be broadcast to all nodes, in order to allow all nodes to verify the new node initialization vector
+    let new_node_2_signature_from_node_1 = node_1.accept_new_node(2);
+    let new_node_2_signature_from_node_1 = NewNodeAcceptanceSignature::from_bytes(
+        &new_node_2_signature_from_node_1.to_bytes(&params),
+        &params,
+    )
+    .unwrap();
     println!(
-        "New node 3 signature valid from node 2: {}",
-        new_node_3_signature_from_node_2.is_valid(&node_2_witness, 3)
+        "New node 2 signature valid from node 1: {}",
+        new_node_2_signature_from_node_1.is_valid(&registry.member(1).unwrap().witness, 2, &params)
     );
 
-    let shamir_voting_threshold =
-        ((nodes_currently_in_system_count as f32) * VOTES_THRESHOLD).ceil() as usize;
-    println!(
-        "Accepting a new node... Voting threshold = {}",
-        shamir_voting_threshold
-    );
-    let sss = SSS {
-        threshold: shamir_voting_threshold,
-        share_amount: 1,
-        prime: shamir_prime.clone(),
-    };
-    let s_node_3_combination_index = sss
-        .recover(&[
-            new_node_3_signature_from_node_1.to_shamir_share(),
-            new_node_3_signature_from_node_2.to_shamir_share(),
-        ])
-        .mod_floor(&binomial_coef_s_i_generation);
-    let mut s_i_node_3 = vec![MyBool::from(false); P];
-    for index_to_flip in nth_combination(
-        P,
-        si_weight,
-        s_node_3_combination_index.to_biguint().unwrap(),
-    ) {
-        s_i_node_3[index_to_flip] = MyBool::from(true);
+    // Node 1 accepts the new node 2, then nodes 1 and 2 accept the new node 3
+    let mut nodes = vec![node_1];
+    for new_node_id in [2, 3] {
+        println!(
+            "Accepting a new node... Voting threshold = {}",
+            registry.votes_needed()
+        );
+        for node in &nodes {
+            let signature = node.accept_new_node(new_node_id);
+            // Published when the candidate is announced, before the share is sent to it
+            registry
+                .add_share_binding(
+                    new_node_id,
+                    signature.signing_node_id(),
+                    signature.share_binding(new_node_id),
+                )
+                .unwrap();
+            println!(
+                "New node {} vote from a member accepted: {:?}",
+                new_node_id,
+                registry.add_vote(new_node_id, signature)
+            );
+        }
+        let s_i = registry.admit(new_node_id).unwrap();
+        let node = CertificatelessQcMdpc::init(new_node_id, &params, &s_i).unwrap();
+        let (public_key, witness) = node.public_key_and_witness().unwrap();
+        println!(
+            "Node {}: Public key verified: {}",
+            new_node_id,
+            public_key.check_is_valid(new_node_id, &s_i, &witness, &params)
+        );
+
+        let mut private_key = node.private_key();
+        private_key.set_decoding_algorithm(DecodingAlgorithm::BlackGrayFlip);
+        let encrypted = public_key.encrypt(MESSAGE.as_bytes()).unwrap();
+        let decrypted = private_key.decrypt(&encrypted).unwrap();
+        println!(
+            "Node {}: Decrypted data: {}",
+            new_node_id,
+            std::str::from_utf8(&decrypted).unwrap()
+        );
+
+        registry.register(new_node_id, public_key, witness).unwrap();
+        nodes.push(node);
     }
-    let node_3 = CertificatelessQcMdpc::init(3, P, W, T, &s_i_node_3);
-    nodes_currently_in_system_count += 1;
-    let (node_3_public_key, node_3_witness) = node_3.public_key_and_witness();
     println!(
-        "Node 3: Public key verified: {}",
-        node_3_public_key.check_is_valid(3, &s_i_node_3, &node_3_witness, W)
-    );
-    let node_3_private_key = node_3.private_key();
-    let encrypted = node_3_public_key.encrypt(MESSAGE.as_bytes());
-    let decrypted = node_3_private_key
-        .decrypt(&encrypted)
-        .unwrap();
-    println!(
-        "Node 3: Decrypted data: {}",
-        std::str::from_utf8(&decrypted[0..MESSAGE.len()]).unwrap()
+        "Node 1 vote replayed for node 2: {:?}",
+        registry.add_vote(2, new_node_2_signature_from_node_1)
     );
 
-    println!(
-        "Nodes currently in system: {}",
-        nodes_currently_in_system_count
-    );
+    println!("Nodes currently in system: {}", registry.members_count());
     println!("Time: {:?}", start.elapsed());
 }
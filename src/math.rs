@@ -34,6 +34,47 @@ pub fn binom(n: usize, k: usize) -> BigUint {
     res
 }
 
+pub fn is_prime(n: usize) -> bool {
+    if n < 2 {
+        return false;
+    }
+    (2..).take_while(|&d| d <= n / d).all(|d| !n.is_multiple_of(d))
+}
+
+/// Multiplicative order of `g` modulo the prime `p`, `g` not being a multiple of `p`
+pub fn multiplicative_order(g: usize, p: usize) -> usize {
+    let mut order = p - 1;
+    let mut remaining = p - 1;
+    let mut factor = 2;
+    while remaining > 1 {
+        if factor > remaining / factor {
+            factor = remaining;
+        }
+        while remaining.is_multiple_of(factor) {
+            remaining /= factor;
+            if mod_pow(g, order / factor, p) == 1 {
+                order /= factor;
+            }
+        }
+        factor += 1;
+    }
+    order
+}
+
+fn mod_pow(base: usize, mut exponent: usize, modulus: usize) -> usize {
+    let modulus = modulus as u128;
+    let mut base = base as u128 % modulus;
+    let mut result = 1u128 % modulus;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exponent >>= 1;
+    }
+    result as usize
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -54,4 +95,15 @@ mod tests {
             vec![34, 103, 186, 203, 230, 275, 323]
         );
     }
+
+    #[test]
+    fn test_multiplicative_order() {
+        assert!(super::is_prime(8009));
+        assert!(!super::is_prime(8011 * 3));
+        assert_eq!(super::multiplicative_order(2, 11), 10);
+        assert_eq!(super::multiplicative_order(2, 7), 3);
+        assert_eq!(super::multiplicative_order(2, 17), 8);
+        assert_eq!(super::multiplicative_order(2, 8009), 4004);
+        assert_eq!(super::multiplicative_order(2, 12323), 12322);
+    }
 }
@@ -1,10 +1,19 @@
 use num::{One, Zero};
 use std::fmt::Display;
 use std::ops::{Add, AddAssign, Deref, DerefMut, Mul, MulAssign};
+use zeroize::Zeroize;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct MyBool(bool);
 
+impl Zeroize for MyBool {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl Deref for MyBool {
     type Target = bool;
     fn deref(&self) -> &Self::Target {
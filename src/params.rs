@@ -0,0 +1,448 @@
+use crate::certificateless_qc_mdpc::KOBARA_IMAI_MIN_MESSAGE_WORD_SIZE;
+use crate::math::{is_prime, multiplicative_order};
+use crate::Error;
+use num::integer::Roots;
+use num_bigint::BigUint;
+use num_primes::Verification;
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Upper bound on `p`, well above the largest preset. Checking that `p` is prime and computing
+/// the order of 2 modulo `p` take up to `sqrt(p)` steps, and parameter sets can come from
+/// untrusted armored text.
+const MAX_P: usize = 1 << 17;
+
+/// Size in bytes of [`Params::identifier`]
+pub const PARAMS_IDENTIFIER_SIZE: usize = 8;
+
+/// Shamir primes already found by [`Params::shamir_prime`], by `sig_n`
+static SHAMIR_PRIMES: Mutex<BTreeMap<usize, BigUint>> = Mutex::new(BTreeMap::new());
+
+/// Parameters of the QC-MDPC encryption scheme, of the KKS signatures used to vote for new nodes,
+/// and of the vote itself
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Params {
+    /// Encryption code length, multiplied by code dimension. This is the inverse of the code rate
+    pub n_0: usize,
+    /// Encryption code dimension, size of the circulant blocks
+    pub p: usize,
+    /// Parity-check matrix weight
+    pub w: usize,
+    /// Errors count
+    pub t: usize,
+    /// Vote threshold for new node acceptance
+    pub votes_threshold: f32,
+    /// Signature secret generator dimension
+    pub sig_k: usize,
+    /// Signature public parity-check matrix length
+    pub sig_n: usize,
+    /// Signature secret generator length
+    pub sig_n_prime: usize,
+    /// Signature public parity-check matrix dimension
+    pub sig_r: usize,
+    /// Interval for acceptable signature weight. Weight outside this interval will be rejected
+    pub signature_weight_interval: [usize; 2],
+}
+
+impl Params {
+    /// Closest to the legacy [`Self::P8009`], and the default. The Black-Gray-Flip decoder fails
+    /// on 6 to 7% of the errors.
+    pub const P8053: Params = Params {
+        n_0: 2,
+        p: 8053,
+        w: 98,
+        t: 50,
+        votes_threshold: 0.66,
+        sig_k: 160,
+        sig_n: 2000,
+        sig_n_prime: 1000,
+        sig_r: 1100,
+        signature_weight_interval: [470, 530],
+    };
+
+    /// Parameters the scheme was originally designed with, and the former default.
+    /// [`Self::validate`] rejects them, as `x^8009 - 1` has three irreducible factors and `w / 2`
+    /// is even: nodes using them have to generate new keys with [`Self::P8053`].
+    #[deprecated(note = "rejected by Params::validate, generate new keys with Params::P8053")]
+    pub const P8009: Params = Params {
+        p: 8009,
+        w: 100,
+        ..Params::P8053
+    };
+
+    /// Block size of BIKE level 1, without its security level.
+    ///
+    /// The right block of the parity-check matrix is a product of sparse vectors, whose columns
+    /// overlap too much to decode BIKE's errors count. For the three sets with BIKE's block sizes,
+    /// `w` keeps the derived weights odd and the two blocks balanced, and `t` is lowered so that
+    /// decoding mostly succeeds: the Black-Gray-Flip decoder failed on 54 out of 400 errors (8
+    /// keys) with this set, 47 with [`Params::P24659`] and 77 with [`Params::P40973`]. That is far
+    /// from BIKE's failure rates, and these sets don't reach BIKE's security levels either.
+    pub const P12323: Params = Params {
+        p: 12323,
+        w: 126,
+        t: 85,
+        ..Params::P8053
+    };
+
+    /// Block size of BIKE level 3, see [`Params::P12323`]
+    pub const P24659: Params = Params {
+        p: 24659,
+        w: 126,
+        t: 130,
+        ..Params::P8053
+    };
+
+    /// Block size of BIKE level 5, see [`Params::P12323`]
+    pub const P40973: Params = Params {
+        p: 40973,
+        w: 126,
+        t: 190,
+        ..Params::P8053
+    };
+
+    /// Encryption code length
+    pub fn n(&self) -> usize {
+        self.n_0 * self.p
+    }
+
+    /// Weight of the initialization vector `s_i` of each node
+    pub fn s_i_weight(&self) -> usize {
+        (self.w >> 1).nth_root(2)
+    }
+
+    /// Weight of `h_i_1`, derived from the node identifier
+    pub fn h_i_1_weight(&self) -> usize {
+        (self.w >> 1).nth_root(3)
+    }
+
+    /// Weight of the secret `h_i_2`
+    pub fn h_i_2_weight(&self) -> usize {
+        (self.w >> 1).nth_root(3)
+    }
+
+    /// Weight of the secret `h_i_3`, the left block of the parity-check matrix
+    pub fn h_i_3_weight(&self) -> usize {
+        self.w >> 1
+    }
+
+    /// Upper bound on the weight of both circulant blocks of the parity-check matrix: `h_i_3`
+    /// on the left, and the product of `h_i_2`, `h_i_1` and `s_i` on the right
+    pub fn max_block_weight(&self) -> usize {
+        self.h_i_3_weight()
+            .max(self.h_i_2_weight() * self.h_i_1_weight() * self.s_i_weight())
+    }
+
+    /// Number of irreducible factors of `x^p - 1` over GF(2), `p` being prime: `x - 1`, and
+    /// `(p - 1) / d` factors of degree `d`, `d` being the order of 2 modulo `p`
+    pub fn irreducible_factors_count(&self) -> usize {
+        1 + (self.p - 1) / multiplicative_order(2, self.p)
+    }
+
+    /// Checks the structural conditions the key generation, the encryption and the signatures
+    /// rely on.
+    ///
+    /// As in BIKE, `x^p - 1` must have only two irreducible factors, `x - 1` and
+    /// `1 + x + ... + x^(p - 1)`, so that any odd weight vector is invertible. `w / 2` and the
+    /// weights derived from it, as well as the weight of the KKS matrix `A`, must be odd.
+    pub fn validate(&self) -> Result<(), Error> {
+        #[allow(deprecated)]
+        if (self.p, self.w) == (Params::P8009.p, Params::P8009.w) {
+            return Err(Error::InvalidParameters("p = 8009 and w = 100 is the legacy parameter set, which is no longer supported: generate new keys with Params::P8053"));
+        }
+        if self.n_0 != 2 {
+            return Err(Error::InvalidParameters("n_0 must be 2: the parity-check matrix is made of two circulant blocks"));
+        }
+        if self.p > MAX_P {
+            return Err(Error::InvalidParameters("p must be at most 2^17"));
+        }
+        if !is_prime(self.p) {
+            return Err(Error::InvalidParameters("p must be prime"));
+        }
+        if self.irreducible_factors_count() != 2 {
+            return Err(Error::InvalidParameters("x^p - 1 must have only two irreducible factors over GF(2): 2 must be a primitive root modulo p"));
+        }
+        if self.h_i_3_weight() == 0 || self.h_i_3_weight() > self.p {
+            return Err(Error::InvalidParameters("w / 2 must be between 1 and p"));
+        }
+        if self.h_i_3_weight().is_multiple_of(2) {
+            return Err(Error::InvalidParameters("w / 2 must be odd"));
+        }
+        if self.h_i_1_weight().is_multiple_of(2) || self.h_i_2_weight().is_multiple_of(2) {
+            return Err(Error::InvalidParameters("The cube root of w / 2 must be odd, otherwise h_i_1 and h_i_2 can't be invertible"));
+        }
+        if self.s_i_weight().is_multiple_of(2) {
+            return Err(Error::InvalidParameters("The square root of w / 2 must be odd, otherwise s_i can't be invertible"));
+        }
+        if self.t == 0 || self.t > self.n() {
+            return Err(Error::InvalidParameters("t must be between 1 and n"));
+        }
+        if self.p < KOBARA_IMAI_MIN_MESSAGE_WORD_SIZE {
+            return Err(Error::InvalidParameters("p is too small to encrypt messages"));
+        }
+        if !(self.votes_threshold > 0.0 && self.votes_threshold <= 1.0) {
+            return Err(Error::InvalidParameters("votes_threshold must be in ]0, 1]"));
+        }
+        if self.sig_k == 0 || self.sig_k >= self.sig_n_prime || self.sig_n_prime > self.sig_n {
+            return Err(Error::InvalidParameters("KKS dimensions must satisfy 0 < sig_k < sig_n_prime <= sig_n"));
+        }
+        if self.sig_k.nth_root(3).is_multiple_of(2) {
+            return Err(Error::InvalidParameters("The cube root of sig_k must be odd, otherwise A can't be invertible"));
+        }
+        if self.sig_r >= self.sig_n || self.sig_r > self.p || self.sig_n - self.sig_r > self.p {
+            return Err(Error::InvalidParameters("sig_r and sig_n - sig_r must be at most p, and sig_r lower than sig_n"));
+        }
+        // Signatures are a random combination of the sig_n_prime non-zero columns of G*
+        let [min_weight, max_weight] = self.signature_weight_interval;
+        if min_weight > self.sig_n_prime >> 1
+            || max_weight < self.sig_n_prime >> 1
+            || max_weight > self.sig_n_prime
+        {
+            return Err(Error::InvalidParameters("signature_weight_interval must contain sig_n_prime / 2 and be included in [0, sig_n_prime]"));
+        }
+        Ok(())
+    }
+
+    /// Identifies the parameter set in encoded witnesses and signatures, and in node key backups:
+    /// first bytes of SHA3-256 over the code and KKS dimensions, encoded as u64 LE.
+    /// `votes_threshold` and `signature_weight_interval` only set the voting policy, so changing
+    /// them keeps existing keys and backups valid.
+    pub fn identifier(&self) -> [u8; PARAMS_IDENTIFIER_SIZE] {
+        fields_identifier(&[
+            self.n_0,
+            self.p,
+            self.w,
+            self.t,
+            self.sig_k,
+            self.sig_n,
+            self.sig_n_prime,
+            self.sig_r,
+        ])
+    }
+
+    /// Same as [`Self::identifier`], over `n_0`, `p`, `w` and `t` only: identifies encoded
+    /// encryption keys and ciphertexts, which don't depend on the KKS dimensions
+    pub fn code_identifier(&self) -> [u8; PARAMS_IDENTIFIER_SIZE] {
+        fields_identifier(&[self.n_0, self.p, self.w, self.t])
+    }
+
+    /// Prime of the Shamir secret sharing used to recover `s_i` from the votes: the smallest prime
+    /// larger than `2^sig_n`, so that no `sig_n`-bit share exceeds it. It is derived rather than
+    /// generated at random so that every node recovers the same `s_i`. The search takes a moment
+    /// for large `sig_n`, so it runs once per `sig_n` and process.
+    pub fn shamir_prime(&self) -> BigUint {
+        let mut primes = SHAMIR_PRIMES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        primes
+            .entry(self.sig_n)
+            .or_insert_with(|| {
+                let mut candidate = (BigUint::from(1u8) << self.sig_n) + 1u8;
+                while !Verification::is_prime(&candidate) {
+                    candidate += 2u8;
+                }
+                candidate
+            })
+            .clone()
+    }
+
+    /// Number of votes needed to accept a new node, when `nodes_count` nodes are in the network
+    pub fn votes_needed(&self, nodes_count: usize) -> usize {
+        ((nodes_count as f32) * self.votes_threshold).ceil() as usize
+    }
+}
+
+fn fields_identifier(fields: &[usize]) -> [u8; PARAMS_IDENTIFIER_SIZE] {
+    let mut hasher = Sha3_256::new();
+    for field in fields {
+        hasher.update((*field as u64).to_le_bytes());
+    }
+    hasher.finalize()[..PARAMS_IDENTIFIER_SIZE]
+        .try_into()
+        .unwrap()
+}
+
+#[cfg(test)]
+impl Params {
+    /// Small parameter set for fast tests. Its decoding failure rate is around 1%, so the tests
+    /// draw their randomness from seeded generators.
+    pub(crate) const TEST: Params = Params {
+        n_0: 2,
+        p: 1019,
+        w: 54,
+        t: 6,
+        votes_threshold: 0.66,
+        sig_k: 27,
+        sig_n: 64,
+        sig_n_prime: 32,
+        sig_r: 40,
+        signature_weight_interval: [4, 28],
+    };
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params::P8053
+    }
+}
+
+/// `n_0=2 p=8053 w=98 t=50 votes_threshold=0.66 sig_k=160 sig_n=2000 sig_n_prime=1000 sig_r=1100
+/// signature_weight_interval=470..530`, on a single line
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n_0={} p={} w={} t={} votes_threshold={} sig_k={} sig_n={} sig_n_prime={} sig_r={} signature_weight_interval={}..{}",
+            self.n_0,
+            self.p,
+            self.w,
+            self.t,
+            self.votes_threshold,
+            self.sig_k,
+            self.sig_n,
+            self.sig_n_prime,
+            self.sig_r,
+            self.signature_weight_interval[0],
+            self.signature_weight_interval[1]
+        )
+    }
+}
+
+const INVALID_PARAMS: Error = Error::InvalidEncoding("invalid parameters");
+
+/// Parses the next `name=value` field
+fn parse_field<'a, T: FromStr>(
+    fields: &mut impl Iterator<Item = &'a str>,
+    name: &str,
+) -> Result<T, Error> {
+    fields
+        .next()
+        .and_then(|field| field.strip_prefix(name))
+        .and_then(|field| field.strip_prefix('='))
+        .and_then(|value| value.parse().ok())
+        .ok_or(INVALID_PARAMS)
+}
+
+/// Inverse of the `Display` implementation, the fields must be in the same order
+impl FromStr for Params {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let n_0 = parse_field(&mut fields, "n_0")?;
+        let p = parse_field(&mut fields, "p")?;
+        let w = parse_field(&mut fields, "w")?;
+        let t = parse_field(&mut fields, "t")?;
+        let votes_threshold = parse_field(&mut fields, "votes_threshold")?;
+        let sig_k = parse_field(&mut fields, "sig_k")?;
+        let sig_n = parse_field(&mut fields, "sig_n")?;
+        let sig_n_prime = parse_field(&mut fields, "sig_n_prime")?;
+        let sig_r = parse_field(&mut fields, "sig_r")?;
+        let interval: String = parse_field(&mut fields, "signature_weight_interval")?;
+        let (min_weight, max_weight) = interval.split_once("..").ok_or(INVALID_PARAMS)?;
+        let signature_weight_interval = [
+            min_weight.parse().map_err(|_| INVALID_PARAMS)?,
+            max_weight.parse().map_err(|_| INVALID_PARAMS)?,
+        ];
+        if fields.next().is_some() {
+            return Err(INVALID_PARAMS);
+        }
+        Ok(Self {
+            n_0,
+            p,
+            w,
+            t,
+            votes_threshold,
+            sig_k,
+            sig_n,
+            sig_n_prime,
+            sig_r,
+            signature_weight_interval,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Params, SHAMIR_PRIMES};
+    use num_bigint::BigUint;
+    use crate::Error;
+
+    #[test]
+    fn test_validate() {
+        for params in [
+            Params::P8053,
+            Params::P12323,
+            Params::P24659,
+            Params::P40973,
+        ] {
+            assert_eq!(params.validate(), Ok(()));
+        }
+        assert_eq!(Params::P8053.irreducible_factors_count(), 2);
+        // The original set: x^8009 - 1 has three factors, and w / 2 is even
+        #[allow(deprecated)]
+        let legacy = Params::P8009;
+        assert_eq!(legacy.irreducible_factors_count(), 3);
+        assert!(matches!(
+            legacy.validate(),
+            Err(Error::InvalidParameters(message)) if message.contains("legacy")
+        ));
+        assert!(Params { t: 40, ..legacy }.validate().is_err());
+        assert!(Params { w: 100, ..Params::P8053 }.validate().is_err());
+        assert!(Params { p: 8011 * 3, ..Params::P8053 }.validate().is_err());
+        // Largest prime below 2^64
+        assert!(Params { p: 18446744073709551557, ..Params::P8053 }.validate().is_err());
+        // The order of 2 modulo 7993 is 999
+        assert!(Params { p: 7993, ..Params::P8053 }.validate().is_err());
+        // Cube root of 71 is 4
+        assert!(Params { w: 142, ..Params::P8053 }.validate().is_err());
+        assert!(Params { t: 0, ..Params::P8053 }.validate().is_err());
+        assert!(Params { signature_weight_interval: [510, 530], ..Params::P8053 }.validate().is_err());
+        // Cube root of 343 is 7: votes hash identifiers with SHAKE256, to any sig_k / 2 ones
+        assert_eq!(Params { sig_k: 343, ..Params::P8053 }.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_display_from_str() {
+        for params in [Params::P8053, Params::P40973] {
+            assert_eq!(params.to_string().parse::<Params>(), Ok(params));
+        }
+        assert_eq!(
+            Params::P8053.to_string(),
+            "n_0=2 p=8053 w=98 t=50 votes_threshold=0.66 sig_k=160 sig_n=2000 sig_n_prime=1000 sig_r=1100 signature_weight_interval=470..530"
+        );
+        assert!("n_0=2 p=8053".parse::<Params>().is_err());
+    }
+
+    #[test]
+    fn test_identifier() {
+        let params = Params::P8053;
+        let voting_policy = Params {
+            votes_threshold: 0.5,
+            signature_weight_interval: [450, 550],
+            ..params
+        };
+        assert_eq!(voting_policy.identifier(), params.identifier());
+        assert_eq!(voting_policy.code_identifier(), params.code_identifier());
+        let kks = Params { sig_r: 1000, ..params };
+        assert_ne!(kks.identifier(), params.identifier());
+        assert_eq!(kks.code_identifier(), params.code_identifier());
+        assert_ne!(Params { t: 49, ..params }.code_identifier(), params.code_identifier());
+    }
+
+    #[test]
+    fn test_shamir_prime() {
+        assert_eq!(Params { sig_n: 16, ..Params::P8053 }.shamir_prime(), 65537u32.into());
+        // 2^64 + 13
+        assert_eq!(
+            Params { sig_n: 64, ..Params::P8053 }.shamir_prime(),
+            18446744073709551629u128.into()
+        );
+        assert_eq!(
+            SHAMIR_PRIMES.lock().unwrap().get(&16),
+            Some(&BigUint::from(65537u32))
+        );
+    }
+}
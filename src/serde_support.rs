@@ -0,0 +1,258 @@
+//! Serde implementations using the bit-packed form of vectors and matrices, instead of one
+//! element per bit
+
+use crate::bit_vector::BitVector;
+use crate::my_bool::MyBool;
+use nalgebra::DMatrix;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// `bits` is [`BitVector::to_bytes`]
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "BitVector")]
+struct PackedBitVector {
+    len: usize,
+    #[serde(with = "serde_bytes")]
+    bits: Vec<u8>,
+}
+
+/// Row by row, as in the wire format
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Matrix")]
+struct PackedMatrix {
+    rows: usize,
+    cols: usize,
+    #[serde(with = "serde_bytes")]
+    bits: Vec<u8>,
+}
+
+/// Strict inverse of [`BitVector::to_bytes`]: the padding bits must be zeros
+fn unpack_bits<E: serde::de::Error>(bits: &[u8], len: usize) -> Result<BitVector, E> {
+    let vector = BitVector::from_bytes(bits, len);
+    if vector.to_bytes() != bits {
+        return Err(E::custom("invalid packed bits length or padding"));
+    }
+    Ok(vector)
+}
+
+impl Serialize for BitVector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PackedBitVector {
+            len: self.len(),
+            bits: self.to_bytes(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BitVector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let packed = PackedBitVector::deserialize(deserializer)?;
+        unpack_bits(&packed.bits, packed.len)
+    }
+}
+
+/// For `#[serde(with = "crate::serde_support::packed_matrix")]` on `DMatrix<MyBool>` fields
+pub(crate) mod packed_matrix {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        matrix: &DMatrix<MyBool>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let bits: BitVector = (0..matrix.nrows())
+            .flat_map(|row| (0..matrix.ncols()).map(move |col| *matrix[(row, col)]))
+            .collect();
+        PackedMatrix {
+            rows: matrix.nrows(),
+            cols: matrix.ncols(),
+            bits: bits.to_bytes(),
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DMatrix<MyBool>, D::Error> {
+        let packed = PackedMatrix::deserialize(deserializer)?;
+        let len = packed
+            .rows
+            .checked_mul(packed.cols)
+            .ok_or_else(|| D::Error::custom("matrix too large"))?;
+        let bits = unpack_bits::<D::Error>(&packed.bits, len)?;
+        Ok(DMatrix::from_fn(packed.rows, packed.cols, |row, col| {
+            MyBool::from(bits.get(row * packed.cols + col))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bit_vector::BitVector;
+    use crate::certificateless_qc_mdpc::{
+        test_node, DecodingAlgorithm, HybridCiphertext, KemCiphertext, NewNodeAcceptanceSignature,
+    };
+    use crate::{Error, Params};
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_packed_serialization() {
+        let vector: BitVector = [true, false, true, true, false, false, true, false, true]
+            .into_iter()
+            .collect();
+        let json = serde_json::to_string(&vector).unwrap();
+        assert_eq!(json, r#"{"len":9,"bits":[77,1]}"#);
+        assert_eq!(serde_json::from_str::<BitVector>(&json).unwrap(), vector);
+        // Padding bit set
+        assert!(serde_json::from_str::<BitVector>(r#"{"len":9,"bits":[77,3]}"#).is_err());
+        assert!(serde_json::from_str::<BitVector>(r#"{"len":9,"bits":[77]}"#).is_err());
+    }
+
+    /// JSON round trip of `value`
+    fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+        serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let params = Params::TEST;
+        let mut rng = ChaCha20Rng::seed_from_u64(18);
+        let (node, _) = test_node(1, &params, &mut rng);
+        let (voter, _) = test_node(2, &params, &mut rng);
+        let (public_key, witness) = node.public_key_and_witness().unwrap();
+        let (_, voter_witness) = voter.public_key_and_witness().unwrap();
+
+        for params in [Params::TEST, Params::P8053, Params::P40973] {
+            assert_eq!(round_trip(&params), params);
+        }
+        assert_eq!(round_trip(&public_key), public_key);
+        assert_eq!(round_trip(&witness), witness);
+        assert_eq!(round_trip(&node.backup()), node.backup());
+        assert_eq!(
+            round_trip(&DecodingAlgorithm::BlackGrayFlip),
+            DecodingAlgorithm::BlackGrayFlip
+        );
+
+        let mut private_key = round_trip(&node.private_key());
+        assert!(private_key == node.private_key());
+        private_key.set_decoding_algorithm(DecodingAlgorithm::ConstantTimeBitFlipping);
+        assert_eq!(
+            round_trip(&private_key).decoding_algorithm(),
+            DecodingAlgorithm::ConstantTimeBitFlipping
+        );
+
+        let (kem_ciphertext, shared_secret) = public_key.encapsulate_with_rng(&mut rng);
+        let parsed: KemCiphertext = round_trip(&kem_ciphertext);
+        assert_eq!(parsed, kem_ciphertext);
+        assert_eq!(private_key.decapsulate(&parsed), Ok(shared_secret));
+
+        let hybrid_ciphertext = public_key.hybrid_encrypt_with_rng(b"message", b"ad", &mut rng);
+        let parsed: HybridCiphertext = round_trip(&hybrid_ciphertext);
+        assert_eq!(parsed, hybrid_ciphertext);
+        assert_eq!(private_key.hybrid_decrypt(&parsed, b"ad"), Ok(b"message".to_vec()));
+
+        let signature = voter.accept_new_node(1);
+        let parsed: NewNodeAcceptanceSignature = round_trip(&signature);
+        assert_eq!(parsed, signature);
+        assert_eq!(parsed.verify(&voter_witness, 1, &params), Ok(()));
+    }
+
+    #[test]
+    fn test_mismatched_payloads_are_rejected() {
+        let params = Params::TEST;
+        let mut rng = ChaCha20Rng::seed_from_u64(20);
+        let (node, s_i) = test_node(1, &params, &mut rng);
+        let (voter, _) = test_node(2, &params, &mut rng);
+        let private_key = node.private_key();
+        let (public_key, witness) = node.public_key_and_witness().unwrap();
+        let (_, voter_witness) = voter.public_key_and_witness().unwrap();
+        let longer_vector = |len: usize| serde_json::to_value(BitVector::zeros(len + 1)).unwrap();
+
+        // Ciphertexts and signatures are sized for the parameter set, which serde doesn't know:
+        // they are checked when used
+        let (kem_ciphertext, _) = public_key.encapsulate_with_rng(&mut rng);
+        let parsed = reparse(&kem_ciphertext, "syndrome", longer_vector(params.p)).unwrap();
+        assert_eq!(
+            private_key.decapsulate(&parsed),
+            Err(Error::InvalidLength {
+                expected: params.p,
+                actual: params.p + 1
+            })
+        );
+        let signature = voter.accept_new_node(1);
+        let parsed = reparse(&signature, "signature", longer_vector(params.sig_n)).unwrap();
+        assert_eq!(
+            parsed.verify(&voter_witness, 1, &params),
+            Err(Error::InvalidLength {
+                expected: params.sig_n,
+                actual: params.sig_n + 1
+            })
+        );
+
+        // Key of another parameter set, only differing by t
+        let parsed = reparse(&public_key, "errors_count", json!(params.t + 1)).unwrap();
+        assert_eq!(
+            parsed.verify(1, &s_i, &witness, &params),
+            Err(Error::ParametersMismatch)
+        );
+    }
+
+    /// Serializes `value`, replaces `field` with `replacement` and deserializes it back
+    fn reparse<T: serde::Serialize + serde::de::DeserializeOwned>(
+        value: &T,
+        field: &str,
+        replacement: Value,
+    ) -> Result<T, serde_json::Error> {
+        let mut json = serde_json::to_value(value).unwrap();
+        json[field] = replacement;
+        serde_json::from_value(json)
+    }
+
+    #[test]
+    fn test_deserialization_is_validated() {
+        let params = Params::TEST;
+        let (node, _) = test_node(1, &params, &mut ChaCha20Rng::seed_from_u64(19));
+        let private_key = node.private_key();
+        let (public_key, witness) = node.public_key_and_witness().unwrap();
+        let empty_vector = json!({"len": 0, "bits": []});
+        let small_matrix = json!({"rows": 1, "cols": 1, "bits": [1]});
+
+        for (field, replacement) in [
+            // Used to loop forever when drawing error vectors
+            ("errors_count", json!(3000)),
+            ("errors_count", json!(0)),
+            // Used to panic when encrypting
+            ("max_message_size_bits", json!(1018)),
+            ("encoded_vector_size", json!(2037)),
+            ("generator_first_line", empty_vector.clone()),
+        ] {
+            assert!(reparse(&public_key, field, replacement).is_err(), "{}", field);
+        }
+
+        let left_block = serde_json::to_value(&private_key).unwrap()["parity_check_blocks"][0].take();
+        for (field, replacement) in [
+            // Used to panic on the first decryption
+            ("parity_check_blocks", json!([])),
+            ("parity_check_blocks", json!([left_block, empty_vector])),
+            ("expected_encoded_vector_size", json!(1019)),
+            ("errors_count", json!(3000)),
+            ("padded_block_weight", json!(1)),
+            // Sets the length of the constant-time decoding loops
+            ("padded_block_weight", json!(usize::MAX)),
+            ("padded_block_weight", json!(params.max_block_weight() + 1)),
+        ] {
+            assert!(reparse(&private_key, field, replacement).is_err(), "{}", field);
+        }
+
+        for (field, replacement) in [
+            // The parity-check matrix must be the one derived from the witness vector
+            ("pubkey_witness_vector", serde_json::to_value(BitVector::zeros(params.p)).unwrap()),
+            ("signature_parity_matrix", small_matrix.clone()),
+            ("signature_multiplication_matrix", small_matrix),
+        ] {
+            assert!(reparse(&witness, field, replacement).is_err(), "{}", field);
+        }
+    }
+}